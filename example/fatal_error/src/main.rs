@@ -2,10 +2,17 @@ use alfred_workflow_rust_project::workflow::AlfredWorkflow;
 use alfred_workflow_rust_project::workflow_item::WorkflowItem;
 
 fn main() {
+    let mut workflow = AlfredWorkflow::init().unwrap();
 
-    AlfredWorkflow::init()
-        .fatal_error("errorCode","errorMsg")
-        .add_item(WorkflowItem::new("newItem")
-            .subtitle("cccc"))
-        .send_feedback();
+    match workflow.load("not_exist_cache") {
+        Ok(content) => workflow
+            .add_item(WorkflowItem::new("cache")
+                .subtitle(content.as_str()))
+            .send_feedback()
+            .unwrap(),
+        Err(e) => workflow
+            .present_error("CacheError", &e)
+            .send_feedback()
+            .unwrap(),
+    }
 }
@@ -4,10 +4,12 @@ use alfred_workflow_rust_project::workflow::AlfredWorkflow;
 fn main() {
     let config_key = "test_set_config";
 
-    let alfred = Alfred::init();
-    let workflow = AlfredWorkflow::init();
+    let alfred = Alfred::init().unwrap();
+    let workflow = AlfredWorkflow::init().unwrap();
 
     let bundle_id = workflow.get_workflow_bundle_id();
-    alfred.set_config(bundle_id.as_str(), config_key, "cc");
+    alfred
+        .set_config(bundle_id.as_str(), config_key, "cc")
+        .unwrap();
     print!("ccccc.........")
 }
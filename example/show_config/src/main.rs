@@ -3,7 +3,7 @@ use alfred_workflow_rust_project::workflow::AlfredWorkflow;
 use alfred_workflow_rust_project::workflow_item::WorkflowItem;
 
 fn main() {
-    let workflow = AlfredWorkflow::init();
+    let workflow = AlfredWorkflow::init().unwrap();
     let cache_path = workflow.get_workflow_cache_path();
     let bundle_id = workflow.get_workflow_bundle_id();
     let uuid = workflow.get_workflow_uuid();
@@ -47,5 +47,6 @@ fn main() {
             .subtitle(theme_select_background.as_str()))
         .add_item(WorkflowItem::new("config_env")
             .subtitle(test_config_key.as_str()))
-        .send_feedback();
+        .send_feedback()
+        .unwrap();
 }
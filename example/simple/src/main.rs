@@ -3,7 +3,7 @@ use alfred_workflow_rust_project::icon::{BuiltinIcon, Icon};
 use alfred_workflow_rust_project::workflow_item::{Action, ActionItem, ItemText, ItemType, Modifier, ModKey, WorkflowItem};
 
 fn main() {
-    let mut simple_wf = alfred_workflow_rust_project::workflow::AlfredWorkflow::init();
+    let mut simple_wf = alfred_workflow_rust_project::workflow::AlfredWorkflow::init().unwrap();
     simple_wf = simple_wf
         .add_item(WorkflowItem::new("Simple Item")
             .subtitle("The item only has the subTitle"))
@@ -79,5 +79,5 @@ fn main() {
                 .subtitle("vars defined")
                 .args("won't use but must have it")
                 .vars("test_var","var defined in mods")));
-    simple_wf.send_feedback()
+    simple_wf.send_feedback().unwrap()
 }
@@ -1,76 +1,124 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
+use crate::alfred_context::AlfredContext;
 use crate::alfred_logger::Logger;
+use crate::alfred_script::{applescript_string, js_string, OsaScriptRunner, Script, ScriptRunner};
+use crate::alfred_trigger::ExternalTrigger;
+use crate::workflow_error::Result;
 
 #[derive(Serialize, Deserialize)]
-pub struct Alfred {}
+pub struct Alfred {
+    #[serde(skip)]
+    context: AlfredContext,
+    #[serde(skip, default = "default_runner")]
+    runner: Arc<dyn ScriptRunner>,
+    // wait for the control scripts this long, none to detach them
+    #[serde(skip)]
+    timeout: Option<Duration>,
+}
+
+fn default_runner() -> Arc<dyn ScriptRunner> {
+    Arc::new(OsaScriptRunner::new())
+}
 
 impl Alfred {
-    pub fn init() -> Alfred {
-        Logger::init();
-        Alfred {}
-    }
-
-    pub fn search(&self, query: &str) {
-        let script = format!("Application(\"{}\").search(\"{}\");", self.get_app_name(), query);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-l", "JavaScript", "-e", script.as_str()])
-            .spawn();
-    }
-    pub fn action(&self, query: &str) {
-        let script = format!("Application(\"{}\").action(\"{}\");", self.get_app_name(), query);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-l", "JavaScript", "-e", script.as_str()])
-            .spawn();
-    }
-    pub fn action_with_types(&self, query: &str, action_type: &str) {
-        let script = format!("Application(\"{}\").action(\"{}\",\"{}\");", self.get_app_name(), query, action_type);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-l", "JavaScript", "-e", script.as_str()])
-            .spawn();
-    }
-    pub fn browse(&self, query: &str) {
-        let script = format!("Application(\"{}\").browse(\"{}\");", self.get_app_name(), query);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-l", "JavaScript", "-e", script.as_str()])
-            .spawn();
-    }
-    pub fn set_theme(&self, theme: &str) {
-        let script = format!("Application(\"{}\").setTheme(\"{}\");", self.get_app_name(), theme);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-l", "JavaScript", "-e", script.as_str()])
-            .spawn();
-    }
-    pub fn reload(&self, workflow: &str) {
-        let script = format!("Application(\"{}\").reloadWorkflow(\"{}\");", self.get_app_name(), workflow);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-l", "JavaScript", "-e", script.as_str()])
-            .spawn();
-    }
-    pub fn trigger(&self, p1: &str, p2: &str) {
-        let script = format!("Application(\"{}\").runTrigger(\"{}\",\"{}\");", self.get_app_name(), p1, p2);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-l", "JavaScript", "-e", script.as_str()])
-            .spawn();
-    }
-    pub fn set_config(&self, bundle: &str, query: &str, query2: &str) {
+    pub fn init() -> Result<Alfred> {
+        Logger::init()?;
+        Ok(Alfred::from_context(AlfredContext::from_env()))
+    }
+
+    // alfred with the given environment, the logger is not set up
+    pub fn from_context(context: AlfredContext) -> Alfred {
+        Alfred {
+            context,
+            runner: default_runner(),
+            timeout: None,
+        }
+    }
+
+    // run the control scripts with another runner(e.g. `RecordingRunner` in tests)
+    pub fn with_runner<R: ScriptRunner + 'static>(mut self, runner: R) -> Alfred {
+        self.runner = Arc::new(runner);
+        self
+    }
+
+    pub fn context(&self) -> &AlfredContext {
+        &self.context
+    }
+
+    // the control methods wait for osascript, at most `timeout`, and return
+    // its failure(alfred not running, unknown workflow etc.) as `WorkflowError::Script`
+    pub fn wait_for(mut self, timeout: Duration) -> Alfred {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // the control methods start osascript and return at once(the default)
+    pub fn detached(mut self) -> Alfred {
+        self.timeout = None;
+        self
+    }
+
+    // run any script like the control methods, its stdout is returned when waiting
+    pub fn run_script(&self, script: &Script) -> Result<String> {
+        match self.timeout {
+            Some(timeout) => self.runner.run_with_timeout(script, timeout),
+            None => self.runner.run(script).map(|_| String::new()),
+        }
+    }
+
+    fn run(&self, script: Script) -> Result<()> {
+        self.run_script(&script).map(|_| ())
+    }
+
+    pub fn search(&self, query: &str) -> Result<()> {
+        let script = format!("Application({}).search({});", js_string(self.get_app_name()), js_string(query));
+        self.run(Script::javascript(script))
+    }
+    pub fn action(&self, query: &str) -> Result<()> {
+        let script = format!("Application({}).action({});", js_string(self.get_app_name()), js_string(query));
+        self.run(Script::javascript(script))
+    }
+    pub fn action_with_types(&self, query: &str, action_type: &str) -> Result<()> {
+        let script = format!("Application({}).action({},{});", js_string(self.get_app_name()), js_string(query), js_string(action_type));
+        self.run(Script::javascript(script))
+    }
+    pub fn browse(&self, query: &str) -> Result<()> {
+        let script = format!("Application({}).browse({});", js_string(self.get_app_name()), js_string(query));
+        self.run(Script::javascript(script))
+    }
+    pub fn set_theme(&self, theme: &str) -> Result<()> {
+        let script = format!("Application({}).setTheme({});", js_string(self.get_app_name()), js_string(theme));
+        self.run(Script::javascript(script))
+    }
+    pub fn reload(&self, workflow: &str) -> Result<()> {
+        let script = format!("Application({}).reloadWorkflow({});", js_string(self.get_app_name()), js_string(workflow));
+        self.run(Script::javascript(script))
+    }
+    // the trigger of the workflow, see `run_trigger` for argument and variables
+    pub fn trigger(&self, name: &str, bundle_id: &str) -> Result<()> {
+        self.run_trigger(&ExternalTrigger::new(name).in_workflow(bundle_id))
+    }
+    // a text variable, see `set_configuration` for typed values, exportable and batches
+    pub fn set_config(&self, bundle: &str, query: &str, query2: &str) -> Result<()> {
         let script = format!("\
-            tell application id \"{}\"
-                 set configuration \"{}\" to value \"{}\" in workflow \"{}\"
+            tell application id {}
+                 set configuration {} to value {} in workflow {}
             end
-        ", self.get_app_name(), query, query2, bundle);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-e", script.as_str()])
-            .spawn();
+        ", applescript_string(self.get_app_name()), applescript_string(query),
+            applescript_string(query2), applescript_string(bundle));
+        self.run(Script::applescript(script))
     }
-    pub fn remove_config(&self, bundle: &str, query: &str) {
+    pub fn remove_config(&self, bundle: &str, query: &str) -> Result<()> {
         let script = format!("\
-            tell application id \"{}\"
-                 remove configuration \"{}\" in workflow \"{}\"
+            tell application id {}
+                 remove configuration {} in workflow {}
             end
-        ", self.get_app_name(), query, bundle);
-        let mut _command = std::process::Command::new("/usr/bin/osascript")
-            .args(["-e", script.as_str()])
-            .spawn();
+        ", applescript_string(self.get_app_name()), applescript_string(query),
+            applescript_string(bundle));
+        self.run(Script::applescript(script))
     }
     pub fn get_app_name(&self) -> &str {
         "com.runningwithcrayons.Alfred"
@@ -79,18 +127,22 @@ impl Alfred {
 
 #[test]
 fn test_alfred_search() {
-    Alfred::init().browse("c");
-    Alfred::init().action("c");
+    let _ = Alfred::init().unwrap().browse("c");
+    let _ = Alfred::init().unwrap().action("c");
 }
 
 #[test]
 fn test_set_config() {
-    Alfred::init().set_config("com.christ.alfred.rust.demo", "test_rust_keu", "ccc")
+    let _ = Alfred::init()
+        .unwrap()
+        .set_config("com.christ.alfred.rust.demo", "test_rust_keu", "ccc");
 }
 
 #[test]
 fn test_remove_config() {
-    Alfred::init().remove_config("com.christ.alfred.rust.demo", "test_rust_keu")
+    let _ = Alfred::init()
+        .unwrap()
+        .remove_config("com.christ.alfred.rust.demo", "test_rust_keu");
 }
 pub trait AlfredEnv {
     fn get_preference_path(&self) -> String;
@@ -102,63 +154,265 @@ pub trait AlfredEnv {
     fn get_workflow_cache_path(&self) -> String;
     fn get_workflow_data_path(&self) -> String;
     fn get_workflow_name(&self) -> String;
+    // added later, the default reads the process env
+    fn get_workflow_description(&self) -> String {
+        std::env::var("alfred_workflow_description").unwrap_or_default()
+    }
+    // added later, the default reads the process env
+    fn get_workflow_keyword(&self) -> String {
+        std::env::var("alfred_workflow_keyword").unwrap_or_default()
+    }
     fn is_debug_mode(&self) -> bool;
     fn get_workflow_uuid(&self) -> String;
     fn get_workflow_version(&self) -> String;
     fn get_theme_background(&self) -> String;
     fn get_theme_selection_background(&self) -> String;
     fn get_theme_subtext(&self) -> String;
+    // added later, alfred always sets its version and the workflow bundle id
+    fn is_running_in_alfred(&self) -> bool {
+        std::env::var("alfred_version").is_ok()
+            && std::env::var("alfred_workflow_bundleid").is_ok()
+    }
 }
 
 impl AlfredEnv for Alfred {
     fn get_theme_subtext(&self) -> String {
-        return std::env::var("alfred_theme_subtext").unwrap_or_default();
+        return self.context.var("alfred_theme_subtext");
     }
     fn get_preference_path(&self) -> String {
-        return std::env::var("alfred_preferences").unwrap_or_default();
+        return self.context.var("alfred_preferences");
     }
 
     fn get_preference_hash_path(&self) -> String {
-        return std::env::var("alfred_preferences_localhash").unwrap_or_default();
+        return self.context.var("alfred_preferences_localhash");
     }
 
     fn get_theme(&self) -> String {
-        return std::env::var("alfred_theme").unwrap_or_default();
+        return self.context.var("alfred_theme");
     }
     fn get_version(&self) -> String {
-        return std::env::var("alfred_version").unwrap_or_default();
+        return self.context.var("alfred_version");
     }
     fn get_version_build(&self) -> String {
-        return std::env::var("alfred_version_build").unwrap_or_default();
+        return self.context.var("alfred_version_build");
     }
 
     fn get_workflow_bundle_id(&self) -> String {
-        return std::env::var("alfred_workflow_bundleid").unwrap_or_default();
+        return self.context.var("alfred_workflow_bundleid");
     }
     fn get_workflow_cache_path(&self) -> String {
-        return std::env::var("alfred_workflow_cache").unwrap_or_default();
+        return self.context.var("alfred_workflow_cache");
     }
     fn get_workflow_data_path(&self) -> String {
-        return std::env::var("alfred_workflow_data").unwrap_or_default();
+        return self.context.var("alfred_workflow_data");
     }
     fn get_workflow_name(&self) -> String {
-        return std::env::var("alfred_workflow_name").unwrap_or_default();
+        return self.context.var("alfred_workflow_name");
+    }
+    fn get_workflow_description(&self) -> String {
+        return self.context.workflow_description().unwrap_or_default().to_string();
+    }
+    fn get_workflow_keyword(&self) -> String {
+        return self.context.workflow_keyword().unwrap_or_default().to_string();
     }
     fn is_debug_mode(&self) -> bool {
-        return std::env::var("alfred_debug").unwrap_or_default().eq("1");
+        return self.context.is_debug_mode();
     }
     fn get_workflow_uuid(&self) -> String {
-        return std::env::var("alfred_workflow_uid").unwrap_or_default();
+        return self.context.var("alfred_workflow_uid");
     }
 
     fn get_workflow_version(&self) -> String {
-        return std::env::var("alfred_workflow_version").unwrap_or_default();
+        return self.context.var("alfred_workflow_version");
     }
 
     fn get_theme_background(&self) -> String {
-        return std::env::var("alfred_theme_background").unwrap_or_default();
+        return self.context.var("alfred_theme_background");
     }
     fn get_theme_selection_background(&self) -> String {
-        return std::env::var("alfred_theme_selection_background").unwrap_or_default();
+        return self.context.var("alfred_theme_selection_background");
+    }
+    fn is_running_in_alfred(&self) -> bool {
+        return self.context.is_running_in_alfred();
+    }
+}
+
+#[cfg(test)]
+mod alfred_test {
+    use std::time::Duration;
+
+    use crate::alfred::{Alfred, AlfredEnv};
+    use crate::alfred_context::AlfredContext;
+    use crate::alfred_script::{RecordingRunner, ScriptError, ScriptErrorKind, ScriptLanguage};
+    use crate::workflow_error::WorkflowError;
+
+    fn alfred() -> (Alfred, RecordingRunner) {
+        let runner = RecordingRunner::new();
+        let alfred = Alfred::from_context(AlfredContext::default()).with_runner(runner.clone());
+        (alfred, runner)
+    }
+
+    #[test]
+    fn test_javascript_controls() {
+        let (alfred, runner) = alfred();
+        alfred.search("rust").unwrap();
+        alfred.action("/tmp").unwrap();
+        alfred.action_with_types("/tmp", "file").unwrap();
+        alfred.browse("~/Desktop").unwrap();
+        alfred.set_theme("Alfred Classic").unwrap();
+        alfred.reload("com.demo.wf").unwrap();
+        alfred.trigger("refresh", "com.demo.wf").unwrap();
+
+        let scripts = runner.scripts();
+        assert!(scripts.iter().all(|s| s.language == ScriptLanguage::JAVASCRIPT));
+        let sources: Vec<&str> = scripts.iter().map(|s| s.source.as_str()).collect();
+        assert_eq!(
+            sources,
+            vec![
+                r#"Application("com.runningwithcrayons.Alfred").search("rust");"#,
+                r#"Application("com.runningwithcrayons.Alfred").action("/tmp");"#,
+                r#"Application("com.runningwithcrayons.Alfred").action("/tmp","file");"#,
+                r#"Application("com.runningwithcrayons.Alfred").browse("~/Desktop");"#,
+                r#"Application("com.runningwithcrayons.Alfred").setTheme("Alfred Classic");"#,
+                r#"Application("com.runningwithcrayons.Alfred").reloadWorkflow("com.demo.wf");"#,
+                r#"Application("com.runningwithcrayons.Alfred").runTrigger("refresh", {inWorkflow: "com.demo.wf"});"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wait_surfaces_errors() {
+        let runner = RecordingRunner::new().fail_with(ScriptError::from_stderr(
+            "0:51: execution error: Error: Application isn't running. (-600)",
+        ));
+        let alfred = Alfred::from_context(AlfredContext::default()).with_runner(runner.clone());
+        // detached runs can not know
+        assert!(alfred.search("a").is_ok());
+
+        let alfred = alfred.wait_for(Duration::from_secs(1));
+        match alfred.search("a") {
+            Err(WorkflowError::Script(e)) => {
+                assert_eq!(e.kind, ScriptErrorKind::UNAVAILABLE);
+                assert_eq!(e.code, Some(-600));
+            }
+            _ => panic!("the script error is expected"),
+        }
+        assert!(alfred.detached().reload("com.demo.wf").is_ok());
+        assert_eq!(runner.scripts().len(), 3);
+    }
+
+    #[test]
+    fn test_escape_user_input() {
+        let (alfred, runner) = alfred();
+        alfred.search("\"); Application(\"Finder\").delete(\"/").unwrap();
+        assert_eq!(
+            runner.last().unwrap(),
+            r#"Application("com.runningwithcrayons.Alfred").search("\"); Application(\"Finder\").delete(\"/");"#
+        );
+        alfred.trigger("a\\b", "line\nbreak").unwrap();
+        assert_eq!(
+            runner.last().unwrap(),
+            r#"Application("com.runningwithcrayons.Alfred").runTrigger("a\\b", {inWorkflow: "line\nbreak"});"#
+        );
+
+        alfred
+            .set_config("com.demo.wf", "x\" to value \"y", "v\u{0}")
+            .unwrap();
+        assert!(runner.last().unwrap().contains(
+            r#"set configuration "x\" to value \"y" to value ("v" & (character id 0)) in workflow "com.demo.wf""#
+        ));
+    }
+
+    #[test]
+    fn test_applescript_controls() {
+        let (alfred, runner) = alfred();
+        alfred.set_config("com.demo.wf", "token", "abc").unwrap();
+        alfred.remove_config("com.demo.wf", "token").unwrap();
+
+        let scripts = runner.scripts();
+        assert!(scripts.iter().all(|s| s.language == ScriptLanguage::APPLESCRIPT));
+        assert_eq!(
+            scripts[0].source,
+            "tell application id \"com.runningwithcrayons.Alfred\"
+                 set configuration \"token\" to value \"abc\" in workflow \"com.demo.wf\"
+            end
+        "
+        );
+        assert_eq!(
+            scripts[1].source,
+            "tell application id \"com.runningwithcrayons.Alfred\"
+                 remove configuration \"token\" in workflow \"com.demo.wf\"
+            end
+        "
+        );
+    }
+
+    // an implementation written before the newer methods were added
+    struct OldEnv;
+
+    impl AlfredEnv for OldEnv {
+        fn get_preference_path(&self) -> String {
+            String::new()
+        }
+        fn get_preference_hash_path(&self) -> String {
+            String::new()
+        }
+        fn get_theme(&self) -> String {
+            String::new()
+        }
+        fn get_version(&self) -> String {
+            String::new()
+        }
+        fn get_version_build(&self) -> String {
+            String::new()
+        }
+        fn get_workflow_bundle_id(&self) -> String {
+            String::new()
+        }
+        fn get_workflow_cache_path(&self) -> String {
+            String::new()
+        }
+        fn get_workflow_data_path(&self) -> String {
+            String::new()
+        }
+        fn get_workflow_name(&self) -> String {
+            String::new()
+        }
+        fn is_debug_mode(&self) -> bool {
+            false
+        }
+        fn get_workflow_uuid(&self) -> String {
+            String::new()
+        }
+        fn get_workflow_version(&self) -> String {
+            String::new()
+        }
+        fn get_theme_background(&self) -> String {
+            String::new()
+        }
+        fn get_theme_selection_background(&self) -> String {
+            String::new()
+        }
+        fn get_theme_subtext(&self) -> String {
+            String::new()
+        }
+    }
+
+    #[test]
+    fn test_env_defaults() {
+        let env = OldEnv;
+        assert_eq!(
+            env.get_workflow_keyword(),
+            std::env::var("alfred_workflow_keyword").unwrap_or_default()
+        );
+        assert_eq!(
+            env.get_workflow_description(),
+            std::env::var("alfred_workflow_description").unwrap_or_default()
+        );
+        assert_eq!(
+            env.is_running_in_alfred(),
+            std::env::var("alfred_version").is_ok()
+                && std::env::var("alfred_workflow_bundleid").is_ok()
+        );
     }
 }
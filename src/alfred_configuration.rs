@@ -0,0 +1,243 @@
+use crate::alfred::Alfred;
+use crate::alfred_script::{js_string, Script};
+use crate::workflow_error::{Result, WorkflowError};
+
+// the value of a configuration variable, written as the matching js type
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigValue {
+    TEXT(String),
+    NUMBER(f64),
+    BOOL(bool),
+}
+
+impl ConfigValue {
+    // the js literal, NaN and infinity are refused as alfred can not store them
+    pub fn to_js(&self) -> Result<String> {
+        match self {
+            ConfigValue::TEXT(text) => Ok(js_string(text)),
+            ConfigValue::NUMBER(n) if n.is_finite() => Ok(n.to_string()),
+            ConfigValue::NUMBER(n) => Err(WorkflowError::Invalid(format!(
+                "{} can not be a configuration value",
+                n
+            ))),
+            ConfigValue::BOOL(b) => Ok(b.to_string()),
+        }
+    }
+}
+
+impl From<&str> for ConfigValue {
+    fn from(v: &str) -> Self {
+        ConfigValue::TEXT(v.to_string())
+    }
+}
+
+impl From<String> for ConfigValue {
+    fn from(v: String) -> Self {
+        ConfigValue::TEXT(v)
+    }
+}
+
+impl From<f64> for ConfigValue {
+    fn from(v: f64) -> Self {
+        ConfigValue::NUMBER(v)
+    }
+}
+
+impl From<i64> for ConfigValue {
+    fn from(v: i64) -> Self {
+        ConfigValue::NUMBER(v as f64)
+    }
+}
+
+impl From<i32> for ConfigValue {
+    fn from(v: i32) -> Self {
+        ConfigValue::NUMBER(v as f64)
+    }
+}
+
+impl From<bool> for ConfigValue {
+    fn from(v: bool) -> Self {
+        ConfigValue::BOOL(v)
+    }
+}
+
+// one variable to write
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigVariable {
+    name: String,
+    value: ConfigValue,
+    // none keeps what alfred has
+    exportable: Option<bool>,
+}
+
+impl ConfigVariable {
+    pub fn new<V: Into<ConfigValue>>(name: &str, value: V) -> ConfigVariable {
+        ConfigVariable {
+            name: name.to_string(),
+            value: value.into(),
+            exportable: None,
+        }
+    }
+
+    // whether the variable is exported with the workflow
+    pub fn exportable(mut self, exportable: bool) -> ConfigVariable {
+        self.exportable = Some(exportable);
+        self
+    }
+}
+
+// configuration variables written in one osascript run, by
+// `Alfred::set_configuration`
+// ConfigBatch::new()
+//     .set("api_token", "xyz")
+//     .variable(ConfigVariable::new("page_size", 20).exportable(false))
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigBatch {
+    workflow: Option<String>,
+    variables: Vec<ConfigVariable>,
+}
+
+impl ConfigBatch {
+    pub fn new() -> ConfigBatch {
+        ConfigBatch::default()
+    }
+
+    // the bundle id of the workflow to configure, our own by default
+    pub fn in_workflow(mut self, bundle_id: &str) -> ConfigBatch {
+        self.workflow = Some(bundle_id.to_string());
+        self
+    }
+
+    pub fn set<V: Into<ConfigValue>>(self, name: &str, value: V) -> ConfigBatch {
+        self.variable(ConfigVariable::new(name, value))
+    }
+
+    pub fn variable(mut self, variable: ConfigVariable) -> ConfigBatch {
+        self.variables.push(variable);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty()
+    }
+
+    // one `setConfiguration` call per variable, `own_bundle_id` is used when
+    // no workflow is given
+    pub fn to_script(&self, app: &str, own_bundle_id: Option<&str>) -> Result<Script> {
+        let workflow = self
+            .workflow
+            .as_deref()
+            .or(own_bundle_id)
+            .filter(|w| !w.is_empty())
+            .ok_or_else(|| {
+                WorkflowError::Invalid(
+                    "no workflow to configure, it is not run by alfred".to_string(),
+                )
+            })?;
+
+        let mut lines = vec![format!("const alfred = Application({});", js_string(app))];
+        for variable in &self.variables {
+            let mut options = vec![
+                format!("toValue: {}", variable.value.to_js()?),
+                format!("inWorkflow: {}", js_string(workflow)),
+            ];
+            if let Some(exportable) = variable.exportable {
+                options.push(format!("exportable: {}", exportable));
+            }
+            lines.push(format!(
+                "alfred.setConfiguration({}, {{{}}});",
+                js_string(&variable.name),
+                options.join(", ")
+            ));
+        }
+        Ok(Script::javascript(lines.join("\n")))
+    }
+}
+
+impl Alfred {
+    // write all the variables at once, nothing is run for an empty batch
+    pub fn set_configuration(&self, batch: &ConfigBatch) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let bundle_id = self.context().workflow_bundle_id();
+        let script = batch.to_script(self.get_app_name(), bundle_id)?;
+        self.run_script(&script).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod alfred_configuration_test {
+    use std::collections::HashMap;
+
+    use crate::alfred::Alfred;
+    use crate::alfred_configuration::{ConfigBatch, ConfigValue, ConfigVariable};
+    use crate::alfred_context::AlfredContext;
+    use crate::alfred_script::{RecordingRunner, ScriptLanguage};
+
+    const APP: &str = "com.runningwithcrayons.Alfred";
+
+    #[test]
+    fn test_value_literals() {
+        assert_eq!(ConfigValue::from("a\"b").to_js().unwrap(), r#""a\"b""#);
+        assert_eq!(ConfigValue::from(20).to_js().unwrap(), "20");
+        assert_eq!(ConfigValue::from(-1.5).to_js().unwrap(), "-1.5");
+        assert_eq!(ConfigValue::from(true).to_js().unwrap(), "true");
+        assert!(ConfigValue::from(f64::NAN).to_js().is_err());
+        assert!(ConfigValue::from(f64::INFINITY).to_js().is_err());
+    }
+
+    #[test]
+    fn test_batch_script() {
+        let batch = ConfigBatch::new()
+            .set("api_token", "xyz")
+            .variable(ConfigVariable::new("page_size", 20).exportable(false))
+            .variable(ConfigVariable::new("debug", true).exportable(true));
+        let script = batch.to_script(APP, Some("com.demo.wf")).unwrap();
+        assert_eq!(script.language, ScriptLanguage::JAVASCRIPT);
+        assert_eq!(
+            script.source,
+            r#"const alfred = Application("com.runningwithcrayons.Alfred");
+alfred.setConfiguration("api_token", {toValue: "xyz", inWorkflow: "com.demo.wf"});
+alfred.setConfiguration("page_size", {toValue: 20, inWorkflow: "com.demo.wf", exportable: false});
+alfred.setConfiguration("debug", {toValue: true, inWorkflow: "com.demo.wf", exportable: true});"#
+        );
+
+        let other = ConfigBatch::new()
+            .in_workflow("com.demo.other")
+            .set("a", "b");
+        assert!(other
+            .to_script(APP, None)
+            .unwrap()
+            .source
+            .contains(r#"inWorkflow: "com.demo.other""#));
+        assert!(ConfigBatch::new()
+            .set("a", "b")
+            .to_script(APP, None)
+            .is_err());
+        assert!(ConfigBatch::new()
+            .set("a", f64::NAN)
+            .to_script(APP, Some("com.demo.wf"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_configuration_in_one_run() {
+        let runner = RecordingRunner::new();
+        let context = AlfredContext::from_map(HashMap::from([(
+            "alfred_workflow_bundleid".to_string(),
+            "com.demo.wf".to_string(),
+        )]));
+        let alfred = Alfred::from_context(context).with_runner(runner.clone());
+
+        alfred.set_configuration(&ConfigBatch::new()).unwrap();
+        assert!(runner.scripts().is_empty());
+
+        alfred
+            .set_configuration(&ConfigBatch::new().set("a", 1).set("b", false))
+            .unwrap();
+        let scripts = runner.scripts();
+        assert_eq!(scripts.len(), 1);
+        assert_eq!(scripts[0].source.matches("setConfiguration").count(), 2);
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::version::Version;
+
+// every variable alfred sets for the workflow scripts
+pub const ALFRED_VARIABLES: [&str; 17] = [
+    "alfred_preferences",
+    "alfred_preferences_localhash",
+    "alfred_theme",
+    "alfred_theme_background",
+    "alfred_theme_selection_background",
+    "alfred_theme_subtext",
+    "alfred_version",
+    "alfred_version_build",
+    "alfred_workflow_bundleid",
+    "alfred_workflow_cache",
+    "alfred_workflow_data",
+    "alfred_workflow_name",
+    "alfred_workflow_description",
+    "alfred_workflow_version",
+    "alfred_workflow_uid",
+    "alfred_workflow_keyword",
+    "alfred_debug",
+];
+
+// a snapshot of the environment alfred runs the workflow with
+// read once, so it could also be built from a map in tests
+#[derive(Clone, Debug, Default)]
+pub struct AlfredContext {
+    vars: HashMap<String, String>,
+    preferences: Option<PathBuf>,
+    preferences_localhash: Option<String>,
+    theme: Option<String>,
+    theme_background: Option<String>,
+    theme_selection_background: Option<String>,
+    theme_subtext: Option<u8>,
+    version: Option<Version>,
+    version_build: Option<u32>,
+    workflow_bundle_id: Option<String>,
+    workflow_cache: Option<PathBuf>,
+    workflow_data: Option<PathBuf>,
+    workflow_name: Option<String>,
+    workflow_description: Option<String>,
+    workflow_keyword: Option<String>,
+    workflow_uid: Option<String>,
+    workflow_version: Option<String>,
+    debug: bool,
+}
+
+impl AlfredContext {
+    pub fn from_env() -> AlfredContext {
+        AlfredContext::from_map(std::env::vars().collect())
+    }
+
+    pub fn from_map(vars: HashMap<String, String>) -> AlfredContext {
+        let text = |key: &str| -> Option<String> {
+            vars.get(key)
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+        let path = |key: &str| text(key).map(PathBuf::from);
+
+        AlfredContext {
+            preferences: path("alfred_preferences"),
+            preferences_localhash: text("alfred_preferences_localhash"),
+            theme: text("alfred_theme"),
+            theme_background: text("alfred_theme_background"),
+            theme_selection_background: text("alfred_theme_selection_background"),
+            theme_subtext: text("alfred_theme_subtext").and_then(|v| v.parse().ok()),
+            version: text("alfred_version").and_then(|v| Version::from_alfred_version(&v).ok()),
+            version_build: text("alfred_version_build").and_then(|v| v.parse().ok()),
+            workflow_bundle_id: text("alfred_workflow_bundleid"),
+            workflow_cache: path("alfred_workflow_cache"),
+            workflow_data: path("alfred_workflow_data"),
+            workflow_name: text("alfred_workflow_name"),
+            workflow_description: text("alfred_workflow_description"),
+            workflow_keyword: text("alfred_workflow_keyword"),
+            workflow_uid: text("alfred_workflow_uid"),
+            workflow_version: text("alfred_workflow_version"),
+            debug: text("alfred_debug").map(|v| v == "1").unwrap_or(false),
+            vars,
+        }
+    }
+
+    // the raw value of any variable(alfred's or the workflow's), empty if not set
+    pub fn var(&self, key: &str) -> String {
+        self.vars.get(key).cloned().unwrap_or_default()
+    }
+
+    pub fn vars(&self) -> &HashMap<String, String> {
+        &self.vars
+    }
+
+    pub fn preferences(&self) -> Option<&Path> {
+        self.preferences.as_deref()
+    }
+
+    pub fn preferences_localhash(&self) -> Option<&str> {
+        self.preferences_localhash.as_deref()
+    }
+
+    pub fn theme(&self) -> Option<&str> {
+        self.theme.as_deref()
+    }
+
+    pub fn theme_background(&self) -> Option<&str> {
+        self.theme_background.as_deref()
+    }
+
+    pub fn theme_selection_background(&self) -> Option<&str> {
+        self.theme_selection_background.as_deref()
+    }
+
+    pub fn theme_subtext(&self) -> Option<u8> {
+        self.theme_subtext
+    }
+
+    pub fn version(&self) -> Option<&Version> {
+        self.version.as_ref()
+    }
+
+    pub fn version_build(&self) -> Option<u32> {
+        self.version_build
+    }
+
+    pub fn workflow_bundle_id(&self) -> Option<&str> {
+        self.workflow_bundle_id.as_deref()
+    }
+
+    pub fn workflow_cache(&self) -> Option<&Path> {
+        self.workflow_cache.as_deref()
+    }
+
+    pub fn workflow_data(&self) -> Option<&Path> {
+        self.workflow_data.as_deref()
+    }
+
+    pub fn workflow_name(&self) -> Option<&str> {
+        self.workflow_name.as_deref()
+    }
+
+    // set since alfred 4.0
+    pub fn workflow_description(&self) -> Option<&str> {
+        self.workflow_description.as_deref()
+    }
+
+    // the keyword the script filter is invoked by, set since alfred 5.0
+    pub fn workflow_keyword(&self) -> Option<&str> {
+        self.workflow_keyword.as_deref()
+    }
+
+    pub fn workflow_uid(&self) -> Option<&str> {
+        self.workflow_uid.as_deref()
+    }
+
+    pub fn workflow_version(&self) -> Option<&str> {
+        self.workflow_version.as_deref()
+    }
+
+    pub fn is_debug_mode(&self) -> bool {
+        self.debug
+    }
+
+    // alfred always sets its version and the workflow bundle id
+    pub fn is_running_in_alfred(&self) -> bool {
+        self.version.is_some() && self.workflow_bundle_id.is_some()
+    }
+
+    // whether the running alfred is `min_version` or newer
+    // unknown alfred version is treated as new enough
+    pub fn is_alfred_since(&self, min_version: &str) -> bool {
+        match (
+            self.version.as_ref(),
+            Version::from_alfred_version(min_version),
+        ) {
+            (Some(v), Ok(min)) => v.gt(&min) || v.eq(&min),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod alfred_context_test {
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    use crate::alfred_context::{AlfredContext, ALFRED_VARIABLES};
+    use crate::version::Version;
+
+    fn env_map() -> HashMap<String, String> {
+        [
+            ("alfred_preferences", "/Users/x/Alfred.alfredpreferences"),
+            ("alfred_theme_subtext", "3"),
+            ("alfred_version", "5.0.6"),
+            ("alfred_version_build", "2110"),
+            ("alfred_workflow_bundleid", "com.demo.wf"),
+            ("alfred_workflow_cache", "/tmp/cache"),
+            ("alfred_workflow_data", ""),
+            ("alfred_debug", "1"),
+            ("api_token", "xyz"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_context_from_map() {
+        let ctx = AlfredContext::from_map(env_map());
+        assert_eq!(
+            ctx.preferences(),
+            Some(Path::new("/Users/x/Alfred.alfredpreferences"))
+        );
+        assert_eq!(ctx.theme_subtext(), Some(3));
+        assert!(ctx
+            .version()
+            .unwrap()
+            .eq(&Version::from_alfred_version("5.0.6").unwrap()));
+        assert_eq!(ctx.version_build(), Some(2110));
+        assert_eq!(ctx.workflow_bundle_id(), Some("com.demo.wf"));
+        assert_eq!(ctx.workflow_cache(), Some(Path::new("/tmp/cache")));
+        assert!(ctx.is_debug_mode());
+        assert_eq!(ctx.var("api_token"), "xyz");
+    }
+
+    #[test]
+    fn test_context_missing_or_invalid_values() {
+        let mut map = env_map();
+        map.insert("alfred_version".to_string(), "five".to_string());
+        map.remove("alfred_debug");
+        let ctx = AlfredContext::from_map(map);
+        // empty value is treated as not set
+        assert_eq!(ctx.workflow_data(), None);
+        assert!(ctx.version().is_none());
+        assert_eq!(ctx.theme(), None);
+        assert!(!ctx.is_debug_mode());
+        assert_eq!(ctx.var("not_exist"), "");
+    }
+
+    #[test]
+    fn test_is_running_in_alfred() {
+        assert!(AlfredContext::from_map(env_map()).is_running_in_alfred());
+        assert!(!AlfredContext::default().is_running_in_alfred());
+
+        let mut map = env_map();
+        map.remove("alfred_workflow_bundleid");
+        assert!(!AlfredContext::from_map(map).is_running_in_alfred());
+    }
+
+    #[test]
+    fn test_fields_set_by_newer_alfred() {
+        let mut map = env_map();
+        map.insert("alfred_workflow_keyword".to_string(), "gh".to_string());
+        map.insert(
+            "alfred_workflow_description".to_string(),
+            "demo".to_string(),
+        );
+        let ctx = AlfredContext::from_map(map.clone());
+        assert_eq!(ctx.workflow_keyword(), Some("gh"));
+        assert_eq!(ctx.workflow_description(), Some("demo"));
+
+        // the version only tells when alfred started to set it,
+        // a value that is set is always returned
+        map.insert("alfred_version".to_string(), "4.6.7".to_string());
+        let ctx = AlfredContext::from_map(map.clone());
+        assert!(!ctx.is_alfred_since("5.0"));
+        assert_eq!(ctx.workflow_keyword(), Some("gh"));
+        assert_eq!(ctx.workflow_description(), Some("demo"));
+
+        map.remove("alfred_workflow_keyword");
+        let ctx = AlfredContext::from_map(map);
+        assert_eq!(ctx.workflow_keyword(), None);
+    }
+
+    #[test]
+    fn test_all_variables_read() {
+        let map = ALFRED_VARIABLES
+            .iter()
+            .map(|k| (k.to_string(), "4.8".to_string()))
+            .collect();
+        let ctx = AlfredContext::from_map(map);
+        assert!(ctx.preferences().is_some());
+        assert!(ctx.preferences_localhash().is_some());
+        assert!(ctx.theme().is_some());
+        assert!(ctx.theme_background().is_some());
+        assert!(ctx.theme_selection_background().is_some());
+        assert!(ctx.version().is_some());
+        assert!(ctx.workflow_bundle_id().is_some());
+        assert!(ctx.workflow_cache().is_some());
+        assert!(ctx.workflow_data().is_some());
+        assert!(ctx.workflow_name().is_some());
+        assert!(ctx.workflow_description().is_some());
+        assert!(ctx.workflow_version().is_some());
+        assert!(ctx.workflow_uid().is_some());
+        assert!(ctx.workflow_keyword().is_some());
+    }
+}
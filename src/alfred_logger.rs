@@ -1,3 +1,5 @@
+use std::sync::Mutex;
+
 use log::LevelFilter;
 
 
@@ -11,16 +13,36 @@ use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::Config;
 
+use crate::workflow_error::{Result, WorkflowError};
+
+// the global logger can only be set once per process
+static INITIALIZED: Mutex<bool> = Mutex::new(false);
 
 pub struct Logger {}
 
 
 impl Logger {
-    pub fn init() {
+    // where the workflow log is written
+    pub fn log_path() -> Result<String> {
+        let home = std::env::var("HOME")
+            .map_err(|e| WorkflowError::Logger(format!("HOME: {}", e)))?;
+        Ok(format!("{}/.alfred/ali_workflow.log", home))
+    }
+
+    pub fn init() -> Result<()> {
+        let mut initialized = INITIALIZED
+            .lock()
+            .map_err(|e| WorkflowError::Logger(e.to_string()))?;
+        if *initialized {
+            return Ok(());
+        }
+
         let appender_name = "app_log_appender";
-        let log_path = std::env::var("HOME").unwrap();
-        let log_name = format!("{}/.alfred/ali_workflow.log", log_path);
+        let log_name = Logger::log_path()?;
         let archive_name = log_name.as_str().to_owned() + "{}";
+        let roller = FixedWindowRoller::builder()
+            .build(&archive_name, 10)
+            .map_err(|e| WorkflowError::Logger(e.to_string()))?;
         let config = Config::builder()
             .appender(
                 Appender::builder().build(
@@ -35,14 +57,9 @@ impl Logger {
                                 log_name,
                                 Box::new(CompoundPolicy::new(
                                     Box::new(SizeTrigger::new(1023)),
-                                    Box::new(
-                                        FixedWindowRoller::builder()
-                                            .build(&archive_name, 10)
-                                            .unwrap(),
-                                    ),
+                                    Box::new(roller),
                                 )),
-                            )
-                            .unwrap(),
+                            )?,
                     ),
                 ),
             )
@@ -51,8 +68,10 @@ impl Logger {
                     .appender(appender_name)
                     .build(LevelFilter::Trace),
             )
-            .unwrap();
+            .map_err(|e| WorkflowError::Logger(e.to_string()))?;
 
-        log4rs::init_config(config).unwrap();
+        log4rs::init_config(config).map_err(|e| WorkflowError::Logger(e.to_string()))?;
+        *initialized = true;
+        Ok(())
     }
-}
\ No newline at end of file
+}
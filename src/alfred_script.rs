@@ -0,0 +1,547 @@
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::workflow_error::{Result, WorkflowError};
+
+pub const OSASCRIPT: &str = "/usr/bin/osascript";
+
+// how often a waiting run checks whether osascript is done
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScriptErrorKind {
+    // -600, alfred is not running
+    UNAVAILABLE,
+    // -1728, the object(e.g. a workflow bundle id) does not exist
+    MISSING,
+    // -1743, the user has not allowed automation of alfred
+    FORBIDDEN,
+    // -2700, the script itself failed
+    FAILED,
+    // osascript did not finish in time and was killed
+    TIMEOUT,
+    OTHER,
+}
+
+// a failed osascript run
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub kind: ScriptErrorKind,
+    // the osascript error number, like -600
+    pub code: Option<i32>,
+    // stderr of osascript
+    pub message: String,
+}
+
+impl ScriptError {
+    // from the stderr of osascript, which ends with the error number:
+    // `0:51: execution error: Error: Application isn't running. (-600)`
+    pub fn from_stderr(stderr: &str) -> ScriptError {
+        let message = stderr.trim().to_string();
+        let code = message
+            .rfind("(-")
+            .and_then(|start| {
+                let rest = &message[start + 1..];
+                rest.find(')').map(|end| &rest[..end])
+            })
+            .and_then(|code| code.parse::<i32>().ok());
+        let kind = match code {
+            Some(-600) => ScriptErrorKind::UNAVAILABLE,
+            Some(-1728) => ScriptErrorKind::MISSING,
+            Some(-1743) => ScriptErrorKind::FORBIDDEN,
+            Some(-2700) => ScriptErrorKind::FAILED,
+            _ => ScriptErrorKind::OTHER,
+        };
+        ScriptError {
+            kind,
+            code,
+            message,
+        }
+    }
+
+    pub fn timeout(timeout: Duration) -> ScriptError {
+        ScriptError {
+            kind: ScriptErrorKind::TIMEOUT,
+            code: None,
+            message: format!("osascript did not finish in {:?}", timeout),
+        }
+    }
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ScriptErrorKind::UNAVAILABLE => write!(f, "alfred is not running"),
+            ScriptErrorKind::MISSING => write!(f, "not found: {}", self.message),
+            ScriptErrorKind::FORBIDDEN => write!(f, "not allowed to control alfred"),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScriptLanguage {
+    JAVASCRIPT,
+    APPLESCRIPT,
+}
+
+// a script to run by `osascript`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    pub language: ScriptLanguage,
+    pub source: String,
+}
+
+impl Script {
+    pub fn javascript<S: Into<String>>(source: S) -> Script {
+        Script {
+            language: ScriptLanguage::JAVASCRIPT,
+            source: source.into(),
+        }
+    }
+
+    pub fn applescript<S: Into<String>>(source: S) -> Script {
+        Script {
+            language: ScriptLanguage::APPLESCRIPT,
+            source: source.into(),
+        }
+    }
+
+    // the arguments of `osascript`, applescript is its default language
+    pub fn osascript_args(&self) -> Vec<&str> {
+        match self.language {
+            ScriptLanguage::JAVASCRIPT => vec!["-l", "JavaScript", "-e", self.source.as_str()],
+            ScriptLanguage::APPLESCRIPT => vec!["-e", self.source.as_str()],
+        }
+    }
+}
+
+// `s` as a quoted javascript string literal, any input stays data
+// the output is valid json too, U+2028/U+2029 are escaped for older engines
+pub fn js_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{2028}' | '\u{2029}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// `s` as an applescript string expression
+// applescript only knows the \" \\ \n \r \t escapes, other control
+// characters are joined in as `(character id N)`
+pub fn applescript_string(s: &str) -> String {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => {
+                if !literal.is_empty() {
+                    parts.push(format!("\"{}\"", literal));
+                    literal.clear();
+                }
+                parts.push(format!("(character id {})", c as u32));
+            }
+            c => literal.push(c),
+        }
+    }
+    if !literal.is_empty() || parts.is_empty() {
+        parts.push(format!("\"{}\"", literal));
+    }
+    if parts.len() == 1 {
+        return parts.remove(0);
+    }
+    format!("({})", parts.join(" & "))
+}
+
+// how `Alfred` runs the scripts controlling alfred
+pub trait ScriptRunner: Send + Sync {
+    // start the script and return at once, failures of the script are lost
+    fn run(&self, script: &Script) -> Result<()>;
+
+    // run the script and wait at most `timeout`, stdout is returned
+    fn run_with_timeout(&self, script: &Script, timeout: Duration) -> Result<String>;
+}
+
+// the real one, runs `osascript`
+#[derive(Clone, Debug)]
+pub struct OsaScriptRunner {
+    program: PathBuf,
+}
+
+impl Default for OsaScriptRunner {
+    fn default() -> Self {
+        OsaScriptRunner {
+            program: PathBuf::from(OSASCRIPT),
+        }
+    }
+}
+
+impl OsaScriptRunner {
+    pub fn new() -> OsaScriptRunner {
+        OsaScriptRunner::default()
+    }
+
+    // run another program taking the osascript arguments
+    pub fn program<P: AsRef<Path>>(mut self, program: P) -> OsaScriptRunner {
+        self.program = program.as_ref().to_path_buf();
+        self
+    }
+}
+
+impl ScriptRunner for OsaScriptRunner {
+    fn run(&self, script: &Script) -> Result<()> {
+        let mut child = Command::new(&self.program)
+            .args(script.osascript_args())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // detached: reaped in background, so no zombie is left while the
+        // workflow keeps running, and launchd takes it over once we exit
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    fn run_with_timeout(&self, script: &Script, timeout: Duration) -> Result<String> {
+        let mut child = Command::new(&self.program)
+            .args(script.osascript_args())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        // read both pipes while waiting, a full pipe would block osascript
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+
+        let status = match wait_timeout(&mut child, timeout)? {
+            Some(status) => status,
+            None => {
+                let _ = child.kill();
+                child.wait()?;
+                return Err(WorkflowError::Script(ScriptError::timeout(timeout)));
+            }
+        };
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        if !status.success() {
+            return Err(WorkflowError::Script(ScriptError::from_stderr(&stderr)));
+        }
+        Ok(stdout.trim_end_matches('\n').to_string())
+    }
+}
+
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut out = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut out);
+        }
+        out
+    })
+}
+
+// the exit status, or none when the child is still running after `timeout`
+fn wait_timeout(child: &mut Child, timeout: Duration) -> Result<Option<std::process::ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// keeps the scripts instead of running them, for tests and dry runs
+// the clones share the records, so keep one to look into
+#[derive(Clone, Debug, Default)]
+pub struct RecordingRunner {
+    scripts: Arc<Mutex<Vec<Script>>>,
+    error: Option<ScriptError>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> RecordingRunner {
+        RecordingRunner::default()
+    }
+
+    // the waiting runs fail with the error, like osascript would
+    pub fn fail_with(mut self, error: ScriptError) -> RecordingRunner {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn scripts(&self) -> Vec<Script> {
+        self.scripts.lock().unwrap().clone()
+    }
+
+    // the source of the last script
+    pub fn last(&self) -> Option<String> {
+        self.scripts
+            .lock()
+            .unwrap()
+            .last()
+            .map(|s| s.source.clone())
+    }
+
+    pub fn clear(&self) {
+        self.scripts.lock().unwrap().clear();
+    }
+}
+
+impl ScriptRunner for RecordingRunner {
+    fn run(&self, script: &Script) -> Result<()> {
+        self.scripts.lock().unwrap().push(script.clone());
+        Ok(())
+    }
+
+    fn run_with_timeout(&self, script: &Script, _timeout: Duration) -> Result<String> {
+        self.scripts.lock().unwrap().push(script.clone());
+        match &self.error {
+            Some(error) => Err(WorkflowError::Script(error.clone())),
+            None => Ok(String::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod alfred_script_test {
+    use std::time::{Duration, Instant};
+
+    use proptest::prelude::*;
+
+    use crate::alfred_script::{
+        applescript_string, js_string, OsaScriptRunner, RecordingRunner, Script, ScriptError,
+        ScriptErrorKind, ScriptLanguage, ScriptRunner,
+    };
+    use crate::test_util::TempDir;
+    use crate::workflow_error::WorkflowError;
+
+    fn script_error(result: crate::workflow_error::Result<String>) -> ScriptError {
+        match result {
+            Err(WorkflowError::Script(e)) => e,
+            _ => panic!("a script error is expected"),
+        }
+    }
+
+    // read back what `applescript_string` wrote, like applescript would
+    fn parse_applescript(expr: &str) -> String {
+        let expr = match expr.strip_prefix('(') {
+            Some(inner) if expr.len() > 1 && !expr.starts_with("(character") => {
+                inner.strip_suffix(')').unwrap()
+            }
+            _ => expr,
+        };
+        let mut out = String::new();
+        let mut chars = expr.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => loop {
+                    match chars.next().unwrap() {
+                        '"' => break,
+                        '\\' => match chars.next().unwrap() {
+                            'n' => out.push('\n'),
+                            'r' => out.push('\r'),
+                            't' => out.push('\t'),
+                            c @ ('"' | '\\') => out.push(c),
+                            c => panic!("unknown escape {:?}", c),
+                        },
+                        c => {
+                            assert!(!c.is_control(), "raw control {:?}", c);
+                            out.push(c)
+                        }
+                    }
+                },
+                '(' => {
+                    let rest: String = chars.by_ref().take_while(|c| *c != ')').collect();
+                    let id = rest.strip_prefix("character id ").unwrap();
+                    out.push(char::from_u32(id.parse().unwrap()).unwrap());
+                }
+                ' ' => {
+                    assert_eq!(chars.next(), Some('&'));
+                    assert_eq!(chars.next(), Some(' '));
+                }
+                c => panic!("unexpected {:?} in {}", c, expr),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let cases = [
+            (
+                "execution error: Error: Application isn't running. (-600)",
+                ScriptErrorKind::UNAVAILABLE,
+            ),
+            (
+                "execution error: Can't get workflow \"x\". (-1728)",
+                ScriptErrorKind::MISSING,
+            ),
+            (
+                "execution error: Not authorized to send Apple events to Alfred. (-1743)",
+                ScriptErrorKind::FORBIDDEN,
+            ),
+            (
+                "0:1: execution error: Error: ReferenceError: x is not defined (-2700)",
+                ScriptErrorKind::FAILED,
+            ),
+            (
+                "syntax error: Expected end of line. (-2741)",
+                ScriptErrorKind::OTHER,
+            ),
+            ("killed", ScriptErrorKind::OTHER),
+        ];
+        for (stderr, kind) in cases {
+            assert_eq!(ScriptError::from_stderr(stderr).kind, kind, "{}", stderr);
+        }
+        assert_eq!(
+            ScriptError::from_stderr("(-600) x (-1743)\n").code,
+            Some(-1743)
+        );
+        assert_eq!(ScriptError::from_stderr("killed").code, None);
+    }
+
+    #[test]
+    fn test_run_waiting() {
+        // a fake osascript running the shell body, it gets the osascript arguments
+        let dir = TempDir::new("osascript_waiting");
+        let ok = dir.script("ok", r#"echo "$1 $2 $4""#);
+        let runner = OsaScriptRunner::new().program(&ok);
+        let out = runner
+            .run_with_timeout(&Script::javascript("1+1"), Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(out, "-l JavaScript 1+1");
+
+        let fail = dir.script(
+            "fail",
+            "echo \"0:51: execution error: Error: Application isn't running. (-600)\" >&2; exit 1",
+        );
+        let runner = OsaScriptRunner::new().program(&fail);
+        let e = script_error(
+            runner.run_with_timeout(&Script::applescript("x"), Duration::from_secs(5)),
+        );
+        assert_eq!(e.kind, ScriptErrorKind::UNAVAILABLE);
+        assert!(e.message.contains("isn't running"));
+    }
+
+    #[test]
+    fn test_run_timeout_and_detached() {
+        let dir = TempDir::new("osascript_timeout");
+        let slow = dir.script("slow", "sleep 5");
+        let runner = OsaScriptRunner::new().program(&slow);
+
+        let start = Instant::now();
+        let e = script_error(
+            runner.run_with_timeout(&Script::javascript("x"), Duration::from_millis(200)),
+        );
+        assert_eq!(e.kind, ScriptErrorKind::TIMEOUT);
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        let start = Instant::now();
+        runner.run(&Script::javascript("x")).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_js_string() {
+        assert_eq!(js_string("rust"), r#""rust""#);
+        assert_eq!(js_string(""), r#""""#);
+        assert_eq!(
+            js_string("a\"); Application(\"Finder\").delete(\"~"),
+            r#""a\"); Application(\"Finder\").delete(\"~""#
+        );
+        assert_eq!(
+            js_string("\\ \n \u{0} \u{2028}"),
+            r#""\\ \n \u0000 \u2028""#
+        );
+    }
+
+    #[test]
+    fn test_applescript_string() {
+        assert_eq!(applescript_string("rust"), r#""rust""#);
+        assert_eq!(applescript_string(""), r#""""#);
+        assert_eq!(
+            applescript_string("a\" & do shell script \"rm"),
+            r#""a\" & do shell script \"rm""#
+        );
+        assert_eq!(applescript_string("a\nb\\"), r#""a\nb\\""#);
+        assert_eq!(
+            applescript_string("a\u{0}b"),
+            r#"("a" & (character id 0) & "b")"#
+        );
+        assert_eq!(applescript_string("\u{7}"), "(character id 7)");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_js_string_round_trip(s in any::<String>()) {
+            let literal = js_string(&s);
+            let raw_break = literal.contains(['\n', '\r', '\u{2028}', '\u{2029}']);
+            prop_assert!(!raw_break);
+            prop_assert_eq!(serde_json::from_str::<String>(&literal).unwrap(), s);
+        }
+
+        #[test]
+        fn prop_applescript_string_round_trip(s in any::<String>()) {
+            prop_assert_eq!(parse_applescript(&applescript_string(&s)), s);
+        }
+
+        #[test]
+        fn prop_quotes_and_controls(s in "[\"\\\\\n\r\t\u{0}-\u{1f}a ]*") {
+            prop_assert_eq!(serde_json::from_str::<String>(&js_string(&s)).unwrap(), s.clone());
+            prop_assert_eq!(parse_applescript(&applescript_string(&s)), s);
+        }
+    }
+
+    #[test]
+    fn test_osascript_args() {
+        assert_eq!(
+            Script::javascript("1+1").osascript_args(),
+            vec!["-l", "JavaScript", "-e", "1+1"]
+        );
+        assert_eq!(
+            Script::applescript("beep").osascript_args(),
+            vec!["-e", "beep"]
+        );
+    }
+
+    #[test]
+    fn test_recording_runner() {
+        let runner = RecordingRunner::new();
+        let shared = runner.clone();
+        runner.run(&Script::javascript("a")).unwrap();
+        runner.run(&Script::applescript("b")).unwrap();
+
+        let scripts = shared.scripts();
+        assert_eq!(scripts.len(), 2);
+        assert_eq!(scripts[1].language, ScriptLanguage::APPLESCRIPT);
+        assert_eq!(shared.last().as_deref(), Some("b"));
+        shared.clear();
+        assert!(runner.scripts().is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use crate::alfred::Alfred;
+use crate::alfred_script::{js_string, Script};
+use crate::workflow_error::{Result, WorkflowError};
+
+// an external trigger of a workflow, run by `Alfred::run_trigger`
+// ExternalTrigger::new("refresh")
+//     .argument("all")
+//     .variable("page", "2")
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExternalTrigger {
+    name: String,
+    workflow: Option<String>,
+    argument: Option<String>,
+    // sorted, so the same trigger gives the same script
+    variables: BTreeMap<String, String>,
+}
+
+impl ExternalTrigger {
+    // the trigger id set in the external trigger object
+    pub fn new(name: &str) -> ExternalTrigger {
+        ExternalTrigger {
+            name: name.to_string(),
+            workflow: None,
+            argument: None,
+            variables: BTreeMap::new(),
+        }
+    }
+
+    // the bundle id of the workflow having the trigger, our own by default
+    pub fn in_workflow(mut self, bundle_id: &str) -> ExternalTrigger {
+        self.workflow = Some(bundle_id.to_string());
+        self
+    }
+
+    // passed as `{query}` to the trigger
+    pub fn argument(mut self, argument: &str) -> ExternalTrigger {
+        self.argument = Some(argument.to_string());
+        self
+    }
+
+    pub fn variable(mut self, name: &str, value: &str) -> ExternalTrigger {
+        self.variables.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn variables<I, K, V>(mut self, variables: I) -> ExternalTrigger
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.variables
+            .extend(variables.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    // the jxa call, `own_bundle_id` is used when no workflow is given
+    // Application("...").runTrigger("name", {inWorkflow: "...", withArgument: "...", withVariables: {...}});
+    pub fn to_script(&self, app: &str, own_bundle_id: Option<&str>) -> Result<Script> {
+        let workflow = self
+            .workflow
+            .as_deref()
+            .or(own_bundle_id)
+            .filter(|w| !w.is_empty())
+            .ok_or_else(|| {
+                WorkflowError::Invalid(format!(
+                    "no workflow for the trigger {}, it is not run by alfred",
+                    self.name
+                ))
+            })?;
+
+        let mut options = vec![format!("inWorkflow: {}", js_string(workflow))];
+        if let Some(argument) = &self.argument {
+            options.push(format!("withArgument: {}", js_string(argument)));
+        }
+        if !self.variables.is_empty() {
+            let vars: Vec<String> = self
+                .variables
+                .iter()
+                .map(|(k, v)| format!("{}: {}", js_string(k), js_string(v)))
+                .collect();
+            options.push(format!("withVariables: {{{}}}", vars.join(", ")));
+        }
+        Ok(Script::javascript(format!(
+            "Application({}).runTrigger({}, {{{}}});",
+            js_string(app),
+            js_string(&self.name),
+            options.join(", ")
+        )))
+    }
+}
+
+impl Alfred {
+    pub fn run_trigger(&self, trigger: &ExternalTrigger) -> Result<()> {
+        let bundle_id = self.context().workflow_bundle_id();
+        let script = trigger.to_script(self.get_app_name(), bundle_id)?;
+        self.run_script(&script).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod alfred_trigger_test {
+    use std::collections::HashMap;
+
+    use crate::alfred::Alfred;
+    use crate::alfred_context::AlfredContext;
+    use crate::alfred_script::{RecordingRunner, ScriptLanguage};
+    use crate::alfred_trigger::ExternalTrigger;
+
+    const APP: &str = "com.runningwithcrayons.Alfred";
+
+    fn source(trigger: &ExternalTrigger) -> String {
+        trigger.to_script(APP, Some("com.demo.own")).unwrap().source
+    }
+
+    #[test]
+    fn test_trigger_script() {
+        assert_eq!(
+            source(&ExternalTrigger::new("refresh")),
+            r#"Application("com.runningwithcrayons.Alfred").runTrigger("refresh", {inWorkflow: "com.demo.own"});"#
+        );
+        assert_eq!(
+            source(
+                &ExternalTrigger::new("search")
+                    .in_workflow("com.demo.other")
+                    .argument("rust \"lang\"")
+                    .variable("page", "2")
+                    .variables(HashMap::from([("mode", "all")]))
+            ),
+            r#"Application("com.runningwithcrayons.Alfred").runTrigger("search", {inWorkflow: "com.demo.other", withArgument: "rust \"lang\"", withVariables: {"mode": "all", "page": "2"}});"#
+        );
+    }
+
+    #[test]
+    fn test_trigger_needs_workflow() {
+        let trigger = ExternalTrigger::new("refresh");
+        assert!(trigger.to_script(APP, None).is_err());
+        assert!(trigger.to_script(APP, Some("")).is_err());
+        assert!(trigger
+            .in_workflow("com.demo.wf")
+            .to_script(APP, None)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_run_trigger_in_own_workflow() {
+        let runner = RecordingRunner::new();
+        let context = AlfredContext::from_map(HashMap::from([(
+            "alfred_workflow_bundleid".to_string(),
+            "com.demo.wf".to_string(),
+        )]));
+        let alfred = Alfred::from_context(context).with_runner(runner.clone());
+        alfred
+            .run_trigger(&ExternalTrigger::new("refresh").argument("x"))
+            .unwrap();
+
+        let scripts = runner.scripts();
+        assert_eq!(scripts[0].language, ScriptLanguage::JAVASCRIPT);
+        assert_eq!(
+            scripts[0].source,
+            r#"Application("com.runningwithcrayons.Alfred").runTrigger("refresh", {inWorkflow: "com.demo.wf", withArgument: "x"});"#
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::alfred::Alfred;
+use crate::workflow_error::{Result, WorkflowError};
 use reqwest::blocking::Response;
 use reqwest::header::HeaderMap;
 use serde::Serialize;
@@ -9,91 +10,108 @@ use pbkdf2::{
 use url::Url;
 
 impl Alfred {
-    pub fn get<T: Serialize + ?Sized>(url: &str, params: &T) -> Response {
-        reqwest::blocking::Client::builder()
-            .build()
-            .unwrap()
+    pub fn get<T: Serialize + ?Sized>(url: &str, params: &T) -> Result<Response> {
+        let response = reqwest::blocking::Client::builder()
+            .build()?
             .get(url)
             .query(params)
-            .send()
-            .unwrap()
+            .send()?;
+        Ok(response)
     }
 
-    pub fn post<T: Serialize + ?Sized>(url: &str, headers: HeaderMap) -> Response {
-        reqwest::blocking::Client::builder()
-            .build()
-            .unwrap()
+    pub fn post<T: Serialize + ?Sized>(url: &str, headers: HeaderMap) -> Result<Response> {
+        let response = reqwest::blocking::Client::builder()
+            .build()?
             .post(url)
             .headers(headers)
-            .send()
-            .unwrap()
+            .send()?;
+        Ok(response)
     }
 
-    pub fn get_chrome_cookie(url:&str) {
-        let chrome_encrypt_key = security_framework::passwords::get_generic_password("Chrome Safe Storage", "Chrome")
-            .unwrap_or_default();
-        let salt = Salt::new("c2FsdHlzYWx0").unwrap();
+    pub fn get_chrome_cookie(_url: &str) -> Result<()> {
+        let chrome_encrypt_key =
+            security_framework::passwords::get_generic_password("Chrome Safe Storage", "Chrome")?;
+        let salt = Salt::new("c2FsdHlzYWx0").map_err(|e| WorkflowError::Invalid(e.to_string()))?;
         let params = Params {
             rounds: 1003,
             output_length: 16,
         };
-        let saltKey = Pbkdf2.hash_password_customized(
+        let _salt_key = Pbkdf2
+            .hash_password_customized(
                 &chrome_encrypt_key,
                 Some(Algorithm::Pbkdf2Sha1.ident()),
                 None,
                 params,
                 salt,
             )
-            .unwrap()
+            .map_err(|e| WorkflowError::Invalid(e.to_string()))?
             .hash
-            .unwrap()
+            .ok_or_else(|| WorkflowError::Invalid("empty chrome cookie key".to_string()))?
             .as_ref()
             .to_vec();
+        Ok(())
     }
 }
 
-fn get_chrome_cookie(url: &str, profile: &str) -> Vec<ChromeCookie> {
+fn get_chrome_cookie(url: &str, profile: &str) -> Result<Vec<ChromeCookie>> {
     // parse url
-    let url = Url::parse(url).unwrap();
-    let host = url.domain().unwrap();
+    let url = Url::parse(url)?;
+    let host = url
+        .domain()
+        .ok_or_else(|| WorkflowError::Invalid(format!("no domain in url: {}", url)))?;
 
     // build cookie sql
     let cookie_sql = build_cookie_query_sql(host);
 
     // open chrome cookie db
     // ${process.env.HOME}/Library/Application Support/Google/Chrome/${PROFILE}/Cookies
-    let home_path = std::env::var("HOME").unwrap();
+    let home_path =
+        std::env::var("HOME").map_err(|e| WorkflowError::Invalid(format!("HOME: {}", e)))?;
     let chrome_cookie_db_path =
         home_path + "/Library/Application Support/Google/Chrome/" + profile + "/Cookies";
-    let conn = sqlite::open(chrome_cookie_db_path).unwrap();
+    let conn = sqlite::open(chrome_cookie_db_path)?;
 
     // read cookie
-    let mut cookie_stmt = conn.prepare(cookie_sql).unwrap().into_cursor();
+    let mut cookie_stmt = conn.prepare(cookie_sql)?.into_cursor();
     let mut cookie_rs: Vec<ChromeCookie> = Vec::new();
-    while let Some(state) = cookie_stmt.next().unwrap() {
+    while let Some(state) = cookie_stmt.next()? {
+        let int_at = |idx: usize| -> Result<i64> {
+            state[idx]
+                .as_integer()
+                .ok_or_else(|| WorkflowError::Invalid(format!("cookie column {} is not integer", idx)))
+        };
+        let str_at = |idx: usize| -> Result<String> {
+            state[idx]
+                .as_string()
+                .map(String::from)
+                .ok_or_else(|| WorkflowError::Invalid(format!("cookie column {} is not text", idx)))
+        };
         let cookie = ChromeCookie {
-            creation_utc: state[0].as_integer().unwrap(),
-            host_key: String::from(state[1].as_string().unwrap()),
-            top_frame_site_key: String::from(state[2].as_string().unwrap()),
-            name: String::from(state[3].as_string().unwrap()),
-            value: String::from(state[4].as_string().unwrap()),
-            encrypted_value: state[5].as_binary().unwrap().to_vec(),
-            path: String::from(state[6].as_string().unwrap()),
-            expires_utc: state[7].as_integer().unwrap(),
-            is_secure: state[8].as_integer().unwrap(),
-            is_httponly: state[9].as_integer().unwrap(),
-            last_access_utc: state[10].as_integer().unwrap(),
-            has_expires: state[11].as_integer().unwrap(),
-            is_persistent: state[12].as_integer().unwrap(),
-            priority: state[13].as_integer().unwrap(),
-            samesite: state[14].as_integer().unwrap(),
-            source_scheme: state[15].as_integer().unwrap(),
-            source_port: state[16].as_integer().unwrap(),
-            is_same_part: state[17].as_integer().unwrap(),
+            creation_utc: int_at(0)?,
+            host_key: str_at(1)?,
+            top_frame_site_key: str_at(2)?,
+            name: str_at(3)?,
+            value: str_at(4)?,
+            encrypted_value: state[5]
+                .as_binary()
+                .ok_or_else(|| WorkflowError::Invalid("cookie column 5 is not blob".to_string()))?
+                .to_vec(),
+            path: str_at(6)?,
+            expires_utc: int_at(7)?,
+            is_secure: int_at(8)?,
+            is_httponly: int_at(9)?,
+            last_access_utc: int_at(10)?,
+            has_expires: int_at(11)?,
+            is_persistent: int_at(12)?,
+            priority: int_at(13)?,
+            samesite: int_at(14)?,
+            source_scheme: int_at(15)?,
+            source_port: int_at(16)?,
+            is_same_part: int_at(17)?,
         };
         cookie_rs.push(cookie)
     }
-    return cookie_rs;
+    Ok(cookie_rs)
 }
 
 #[derive(Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
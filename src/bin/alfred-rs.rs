@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use alfred_workflow_rust_project::workflow_bundle::WorkflowBundle;
+use alfred_workflow_rust_project::workflow_package::{WorkflowPackage, EXTENSION};
+use alfred_workflow_rust_project::workflow_simulator::{
+    render, Feedback, Simulator, SimulatorProfile,
+};
+
+const USAGE: &str = "\
+usage: alfred-rs <command> [options]
+
+commands:
+    package     bundle the workflow into an installable .alfredworkflow
+        --plist <file>      info.plist of the workflow(default: ./info.plist)
+        --exec <file>       the built workflow binary
+        --icon <file>       the workflow icon, packaged as icon.png
+        --asset <path>      an extra file or folder, could be repeated
+        --out <path>        the .alfredworkflow file or the folder to put
+                            it in(default: current folder)
+        --check             only check the manifest and list the files
+    inspect <file>  show the metadata and the files of a .alfredworkflow
+    unpack <file>   extract a .alfredworkflow
+        --out <folder>      where to extract(default: the file name
+                            without extension)
+    simulate [query]    run a script filter like alfred and show the items
+        --profile <file>    json or toml with the executable, args and env
+        --exec <file>       the script filter to run(overrides the profile)
+        --once              run the query once, no prompt
+                            at the prompt: `tab N` completes with item N,
+                            `:q` quits, anything else is the new query
+    help        show this message";
+
+type CliResult = Result<(), Box<dyn Error>>;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("package") => package(&args[1..]),
+        Some("inspect") => inspect(&args[1..]),
+        Some("unpack") => unpack(&args[1..]),
+        Some("simulate") => simulate(&args[1..]),
+        Some("help") | Some("-h") | Some("--help") | None => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(cmd) => Err(format!("unknown command `{}`", cmd).into()),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}\n\n{}", e, USAGE);
+        exit(2);
+    }
+}
+
+// the value following the flag
+fn value<'a, I: Iterator<Item = &'a String>>(
+    args: &mut I,
+    flag: &str,
+) -> Result<&'a String, String> {
+    args.next()
+        .ok_or_else(|| format!("`{}` needs a value", flag))
+}
+
+fn package(args: &[String]) -> CliResult {
+    let mut plist = PathBuf::from("info.plist");
+    let mut exec = None;
+    let mut icon = None;
+    let mut assets = Vec::new();
+    let mut out = PathBuf::from(".");
+    let mut check_only = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--plist" => plist = PathBuf::from(value(&mut iter, arg)?),
+            "--exec" => exec = Some(value(&mut iter, arg)?),
+            "--icon" => icon = Some(value(&mut iter, arg)?),
+            "--asset" => assets.push(value(&mut iter, arg)?),
+            "--out" => out = PathBuf::from(value(&mut iter, arg)?),
+            "--check" => check_only = true,
+            _ => return Err(format!("unknown option `{}`", arg).into()),
+        }
+    }
+
+    let mut package = WorkflowPackage::new(&plist);
+    if let Some(exec) = exec {
+        package = package.executable(exec);
+    }
+    if let Some(icon) = icon {
+        package = package.icon(icon);
+    }
+    package = assets.into_iter().fold(package, |p, asset| p.asset(asset));
+
+    let (info, entries) = package.check()?;
+    for entry in &entries {
+        let mode = if entry.executable { "755" } else { "644" };
+        println!("{} {}", mode, entry.name);
+    }
+    if check_only {
+        return Ok(());
+    }
+
+    let path = if out.extension().is_some_and(|e| e == EXTENSION) {
+        package.write_file(&out)?;
+        out
+    } else {
+        package.write_into(&out)?
+    };
+    println!(
+        "packaged {} {} into {}",
+        info.bundle_id.unwrap_or_default(),
+        info.version.unwrap_or_default(),
+        Path::new(&path).display()
+    );
+    Ok(())
+}
+
+// the bundle path, then the options
+fn bundle_path(args: &[String]) -> Result<PathBuf, String> {
+    match args.first() {
+        Some(path) if !path.starts_with("--") => Ok(PathBuf::from(path)),
+        _ => Err("the .alfredworkflow file is missing".to_string()),
+    }
+}
+
+fn inspect(args: &[String]) -> CliResult {
+    let path = bundle_path(args)?;
+    if let Some(arg) = args.get(1) {
+        return Err(format!("unknown option `{}`", arg).into());
+    }
+
+    let mut bundle = WorkflowBundle::open(&path)?;
+    let info = bundle.info()?;
+    let text = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    println!("bundle id:   {}", text(info.bundle_id.clone()));
+    println!("name:        {}", text(info.name.clone()));
+    println!("version:     {}", text(info.version.clone()));
+    println!("created by:  {}", text(info.created_by.clone()));
+    println!("keywords:    {}", info.keywords().join(", "));
+    let variables: Vec<&str> = info.variables.iter().map(|(k, _)| k.as_str()).collect();
+    println!("variables:   {}", variables.join(", "));
+    println!("files:");
+    for file in bundle.files()? {
+        let mode = file
+            .mode
+            .map(|m| format!("{:o}", m & 0o777))
+            .unwrap_or_else(|| "-".to_string());
+        println!("    {:>4} {:>10} {}", mode, file.size, file.name);
+    }
+    Ok(())
+}
+
+fn unpack(args: &[String]) -> CliResult {
+    let path = bundle_path(args)?;
+    let mut out = path.with_extension("");
+
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--out" => out = PathBuf::from(value(&mut iter, arg)?),
+            _ => return Err(format!("unknown option `{}`", arg).into()),
+        }
+    }
+
+    let files = WorkflowBundle::open(&path)?.unpack(&out)?;
+    println!("unpacked {} files into {}", files.len(), out.display());
+    Ok(())
+}
+
+fn simulate(args: &[String]) -> CliResult {
+    let mut profile = SimulatorProfile::default();
+    let mut exec = None;
+    let mut once = false;
+    let mut query = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--profile" => profile = SimulatorProfile::from_file(value(&mut iter, arg)?)?,
+            "--exec" => exec = Some(value(&mut iter, arg)?),
+            "--once" => once = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg).into()),
+            _ => query.push(arg.as_str()),
+        }
+    }
+    if let Some(exec) = exec {
+        profile = profile.executable(exec);
+    }
+
+    let simulator = Simulator::new(profile);
+    let mut query = query.join(" ");
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        let feedback = run_query(&simulator, &query)?;
+        if once {
+            return Ok(());
+        }
+
+        // read the next query, `tab N` completes with the item
+        print!("query> ");
+        std::io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => return Ok(()),
+        };
+        let line = line.trim_end();
+        if line == ":q" {
+            return Ok(());
+        }
+        query = match line.strip_prefix("tab ").map(|n| n.trim().parse::<usize>()) {
+            Some(Ok(n)) => match feedback.auto_complete(n) {
+                Some(auto) => auto.to_string(),
+                None => {
+                    eprintln!("no item {}", n);
+                    query
+                }
+            },
+            _ => line.to_string(),
+        };
+    }
+}
+
+// run the query and follow `rerun` like alfred, up to the profile limit
+fn run_query(simulator: &Simulator, query: &str) -> Result<Feedback, Box<dyn Error>> {
+    println!("> {}", query);
+    let mut feedback = simulator.run(query, &HashMap::new())?;
+    print!("{}", render(&feedback));
+    for _ in 0..simulator.max_reruns() {
+        let delay = match feedback.rerun_delay()? {
+            Some(delay) => delay,
+            None => break,
+        };
+        std::thread::sleep(delay);
+        feedback = simulator.run(query, &feedback.variables)?;
+        print!("{}", render(&feedback));
+    }
+    Ok(feedback)
+}
@@ -19,7 +19,7 @@ impl EnumIdent for IconType {
 
 #[derive(Serialize, Deserialize)]
 pub struct Icon {
-    #[serde(rename = "type")]
+    #[serde(rename = "type", default)]
     icon_type: String,
     path: String,
 }
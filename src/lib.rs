@@ -1,12 +1,17 @@
 extern crate core;
 
 pub mod alfred;
+pub mod alfred_configuration;
+pub mod alfred_context;
 pub mod alfred_logger;
+pub mod alfred_script;
+pub mod alfred_trigger;
 pub mod alfred_web;
 pub mod common;
 pub mod icon;
 pub mod version;
 pub mod workflow;
+pub mod workflow_bundle;
 pub mod workflow_cache;
 pub mod workflow_config;
 pub mod workflow_item;
@@ -14,6 +19,19 @@ pub mod workflow_keychain;
 pub mod workflow_updater;
 pub mod workflow_background;
 pub mod workflow_database;
+pub mod workflow_definition;
+pub mod workflow_error;
+pub mod workflow_filter;
+pub mod workflow_normalize;
+pub mod workflow_package;
+pub mod workflow_panic;
+pub mod workflow_pinyin;
+pub mod workflow_plist;
+pub mod workflow_plist_config;
+pub mod workflow_simulator;
+
+#[cfg(test)]
+mod test_util;
 
 #[cfg(test)]
 mod tests {
@@ -31,13 +49,13 @@ mod workflow_tests {
     #[test]
     fn test_workflow_init_ok() {
         let workflow = AlfredWorkflow::init();
+        assert!(workflow.is_ok());
     }
 
     #[test]
     fn test_workflow_send_feedback_ok() {
-        let workflow = AlfredWorkflow::init();
-        workflow.send_feedback();
-        assert!(true)
+        let workflow = AlfredWorkflow::init().unwrap();
+        assert!(workflow.send_feedback().is_ok());
     }
 }
 
@@ -160,7 +178,7 @@ mod env_tests {
     #[test]
     fn test_alfred_env_get_fine() {
         dotenv::dotenv().ok();
-        let alfred = Alfred::init();
+        let alfred = Alfred::init().unwrap();
         assert_eq!(alfred.get_preference_path(), "alfred_preferences");
         assert_eq!(
             alfred.get_preference_hash_path(),
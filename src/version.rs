@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+use crate::workflow_error::{Result, WorkflowError};
+
 // version eg: v1.2.3-RC
-#[derive(Serialize, Deserialize, PartialEq)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct Version {
     major: u8,
     minor: u8,
@@ -12,53 +14,74 @@ pub struct Version {
 const SUFFIX_CANDIDATE: [&str; 2] = ["M", "RC"];
 
 impl Version {
-    pub fn new(version_str: &str) -> Result<Version, &'static str> {
+    pub fn new(version_str: &str) -> Result<Version> {
+        let format_err =
+            || WorkflowError::Version("version should be like 1.0.0 or 1.2.3 or 1.2.3-RC".to_string());
+
         let vp: Vec<&str> = version_str.split(".").collect();
         if vp.len() != 3 {
-            return Err("version should be like 1.0.0 or 1.2.3 or 1.2.3-RC");
+            return Err(format_err());
         }
 
-        let path_with_build = vp.get(2).unwrap();
+        let path_with_build = vp[2];
         let vb: Vec<&str> = path_with_build.split("-").collect();
         if vb.len() > 2 {
-            return Err("version should be like 1.0.0 or 1.2.3 or 1.2.3-RC");
+            return Err(format_err());
         }
 
-        let major_with_prefix = vp.get(0).unwrap();
+        let major_with_prefix = vp[0];
         let mut major_str = major_with_prefix.to_string();
         if major_str.starts_with("v") {
             major_str = major_str.to_uppercase().replace("V", "");
         }
 
         let version = Version {
-            major: major_str.parse().unwrap(),
-            minor: vp.get(1).unwrap().parse().unwrap(),
-            patch: vb.get(0).unwrap().parse().unwrap(),
+            major: major_str.parse().map_err(|_| format_err())?,
+            minor: vp[1].parse().map_err(|_| format_err())?,
+            patch: vb[0].parse().map_err(|_| format_err())?,
             build: if vb.len() == 2 {
-                Some(vb.get(1).unwrap().to_string())
+                Some(vb[1].to_string())
             } else {
                 None
             },
         };
 
-        if version.build.is_some() {
-            let suffix = version.build.as_ref().unwrap();
-            let mut build_valid = false;
-            for suf in SUFFIX_CANDIDATE {
-                if suffix.starts_with(suf) {
-                    build_valid = true;
-                }
-            }
-            if !build_valid {
-                return Err("Version suffix should be like RC1 or RC2");
+        if let Some(suffix) = version.build.as_ref() {
+            if build_number(suffix).is_none() {
+                return Err(WorkflowError::Version(
+                    "Version suffix should be like RC1 or RC2".to_string(),
+                ));
             }
         }
         Ok(version)
     }
 
+    // alfred reports its version like 4.6 or 5.0.6(no patch sometimes, no build)
+    pub fn from_alfred_version(version_str: &str) -> Result<Version> {
+        let format_err =
+            || WorkflowError::Version("alfred version should be like 5.0 or 5.0.6".to_string());
+
+        let vp: Vec<&str> = version_str.trim().split(".").collect();
+        if vp.len() < 2 || vp.len() > 3 {
+            return Err(format_err());
+        }
+
+        let mut nums: Vec<u8> = Vec::new();
+        for part in vp {
+            nums.push(part.parse().map_err(|_| format_err())?);
+        }
+
+        Ok(Version {
+            major: nums[0],
+            minor: nums[1],
+            patch: nums.get(2).copied().unwrap_or(0),
+            build: None,
+        })
+    }
+
     fn compare(&self, comparison: &Version) -> i8 {
-        let self_v_num = self.major * 100 + self.minor * 10 + self.patch;
-        let comp_v_num = comparison.major * 100 + comparison.minor * 10 + comparison.patch;
+        let self_v_num = (self.major, self.minor, self.patch);
+        let comp_v_num = (comparison.major, comparison.minor, comparison.patch);
         if self_v_num > comp_v_num {
             return 1;
         }
@@ -80,20 +103,12 @@ impl Version {
             return 0;
         }
 
-        let self_build_v: u8 = self
-            .build
-            .as_ref()
-            .unwrap()
-            .replace("RC", "0")
-            .parse()
-            .unwrap();
-        let comparison_build_v: u8 = comparison
+        let self_build_v = self.build.as_deref().and_then(build_number).unwrap_or(0);
+        let comparison_build_v = comparison
             .build
-            .as_ref()
-            .unwrap()
-            .replace("RC", "0")
-            .parse()
-            .unwrap();
+            .as_deref()
+            .and_then(build_number)
+            .unwrap_or(0);
 
         return if self_build_v == comparison_build_v {
             0
@@ -125,6 +140,16 @@ impl Version {
     }
 }
 
+// the number of build suffix, eg: RC => 0, RC2 => 2, M1 => 1
+fn build_number(build: &str) -> Option<u8> {
+    for suf in SUFFIX_CANDIDATE {
+        if let Some(num) = build.strip_prefix(suf) {
+            return if num.is_empty() { Some(0) } else { num.parse().ok() };
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod version_unit_test {
     use crate::version::Version;
@@ -226,6 +251,21 @@ mod version_unit_test {
         assert_eq!(v7.compare(&v6), 1);
     }
 
+    #[test]
+    fn test_parse_alfred_version() {
+        let v = Version::from_alfred_version("5.5").unwrap();
+        assert_eq!(v.major, 5);
+        assert_eq!(v.minor, 5);
+        assert_eq!(v.patch, 0);
+
+        let v2 = Version::from_alfred_version("4.6.7").unwrap();
+        assert_eq!(v2.patch, 7);
+
+        assert!(Version::from_alfred_version("").is_err());
+        assert!(Version::from_alfred_version("5").is_err());
+        assert!(Version::from_alfred_version("5.x").is_err());
+    }
+
     #[test]
     fn test_version_equal() {
         let _v = Version::new("1.2.3").unwrap();
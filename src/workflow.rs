@@ -1,8 +1,16 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::io::{BufWriter, Write};
 use std::process::exit;
 use crate::alfred::{Alfred, AlfredEnv};
 use crate::workflow_item::WorkflowItem;
 use serde::{Deserialize, Serialize};
 use crate::icon::BuiltinIcon;
+use crate::version::Version;
+use crate::alfred_context::AlfredContext;
+use crate::workflow_error::{Result, WorkflowError};
+use crate::workflow_panic;
+use crate::workflow_panic::{FeedbackOut, FeedbackOutput, Sink};
 
 // Alfred workflow object
 #[derive(Serialize, Deserialize)]
@@ -10,22 +18,199 @@ pub struct AlfredWorkflow {
     #[serde(skip_serializing)]
     alfred: Alfred,
     items: Vec<WorkflowItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rerun: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variables: Option<HashMap<String, String>>,
+    #[serde(rename = "skipknowledge", skip_serializing_if = "Option::is_none")]
+    skip_knowledge: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: Option<CachePolicy>,
 }
 
+// results cache handled by alfred itself(since alfred 5.5)
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct CachePolicy {
+    seconds: u32,
+    #[serde(rename = "loosereload", skip_serializing_if = "Option::is_none")]
+    loose_reload: Option<bool>,
+}
+
+// the cache duration range(in seconds) alfred accepts
+const CACHE_MIN_SECONDS: u32 = 5;
+const CACHE_MAX_SECONDS: u32 = 86400;
+// the first alfred release supports script filter cache
+const CACHE_MIN_ALFRED_VERSION: &str = "5.5";
+
+impl CachePolicy {
+    pub fn new(seconds: u32) -> Result<CachePolicy> {
+        if !(CACHE_MIN_SECONDS..=CACHE_MAX_SECONDS).contains(&seconds) {
+            return Err(WorkflowError::Invalid(
+                "cache seconds should be between 5 and 86400".to_string(),
+            ));
+        }
+        Ok(CachePolicy {
+            seconds,
+            loose_reload: None,
+        })
+    }
+
+    // show the stale results first and reload them in background
+    pub fn loose_reload(mut self, loose: bool) -> CachePolicy {
+        self.loose_reload = Some(loose);
+        self
+    }
+}
+
+// whether the alfred(version like 5.5 or 5.5.1) could handle `cache`
+fn is_cache_supported(alfred_version: &str) -> bool {
+    let min = Version::from_alfred_version(CACHE_MIN_ALFRED_VERSION).unwrap();
+    match Version::from_alfred_version(alfred_version) {
+        Ok(v) => v.gt(&min) || v.eq(&min),
+        Err(_) => false,
+    }
+}
+
+// top level fields except `items`, written after the streamed items
+#[derive(Serialize)]
+struct FeedbackTail<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rerun: &'a Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    variables: &'a Option<HashMap<String, String>>,
+    #[serde(rename = "skipknowledge", skip_serializing_if = "Option::is_none")]
+    skip_knowledge: &'a Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache: &'a Option<CachePolicy>,
+}
+
+// the interval range(in seconds) alfred accepts for `rerun`
+const RERUN_MIN_SECONDS: f32 = 0.1;
+const RERUN_MAX_SECONDS: f32 = 5.0;
+
 impl AlfredWorkflow {
-    pub fn init() -> AlfredWorkflow {
+    pub fn init() -> Result<AlfredWorkflow> {
+        Ok(AlfredWorkflow::with_alfred(Alfred::init()?))
+    }
+
+    // workflow with the given environment, the logger is not set up
+    pub fn from_context(context: AlfredContext) -> AlfredWorkflow {
+        AlfredWorkflow::with_alfred(Alfred::from_context(context))
+    }
+
+    fn with_alfred(alfred: Alfred) -> AlfredWorkflow {
         AlfredWorkflow {
-            alfred: Alfred::init(),
+            alfred,
             items: Vec::new(),
+            rerun: None,
+            variables: None,
+            skip_knowledge: None,
+            cache: None,
         }
     }
 
+    pub fn context(&self) -> &AlfredContext {
+        self.alfred.context()
+    }
+
+    // init the workflow and report panics as an error item to alfred
+    pub fn init_with_panic_hook() -> Result<AlfredWorkflow> {
+        let workflow = AlfredWorkflow::init()?;
+        workflow_panic::install_panic_hook();
+        Ok(workflow)
+    }
+
     pub fn get_workflow_env(key: &str) -> String {
         std::env::var(key).unwrap_or_default()
     }
 
-    pub fn send_feedback(&self) {
-        print!("{}", serde_json::to_string(self).unwrap())
+    // print the feedback json to stdout for alfred
+    // with the panic hook, nothing is written once a panic was reported
+    pub fn send_feedback(&self) -> Result<()> {
+        self.send_to(workflow_panic::stdout())
+    }
+
+    fn send_to<S: Sink>(&self, output: &FeedbackOutput<S>) -> Result<()> {
+        output.send(|w| self.write_feedback(w))
+    }
+
+    // stream the feedback json into any writer
+    pub fn write_feedback<W: Write>(&self, mut w: W) -> Result<()> {
+        serde_json::to_writer(&mut w, self)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    // print the feedback json to stdout, items are serialized one by one
+    // as the iterator produces them
+    pub fn send_feedback_iter<I, T>(&self, items: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: Borrow<WorkflowItem>,
+    {
+        self.send_iter_to(workflow_panic::stdout(), items)
+    }
+
+    // with the panic hook the parts go through the output shared with the
+    // hook, so a panic on any thread still leaves a valid json
+    fn send_iter_to<S, I, T>(&self, output: &FeedbackOutput<S>, items: I) -> Result<()>
+    where
+        S: Sink,
+        I: IntoIterator<Item = T>,
+        T: Borrow<WorkflowItem>,
+    {
+        if output.is_hooked() {
+            return self.stream_feedback(output.stream(), items);
+        }
+        output.write_with(|w| self.stream_feedback(BufWriter::new(w), items))
+    }
+
+    // stream the feedback json into any writer, the added items come first
+    // and then the items from iterator, nothing is collected in memory
+    pub fn write_feedback_iter<W, I, T>(&self, w: W, items: I) -> Result<()>
+    where
+        W: Write,
+        I: IntoIterator<Item = T>,
+        T: Borrow<WorkflowItem>,
+    {
+        self.stream_feedback(w, items)
+    }
+
+    // every item is serialized first and written as a whole
+    fn stream_feedback<O, I, T>(&self, mut out: O, items: I) -> Result<()>
+    where
+        O: FeedbackOut,
+        I: IntoIterator<Item = T>,
+        T: Borrow<WorkflowItem>,
+    {
+        out.begin()?;
+        let mut buf = Vec::new();
+        let mut first = true;
+        for item in self.items.iter() {
+            item_json(&mut buf, item, first)?;
+            out.item(&buf)?;
+            first = false;
+        }
+        for item in items {
+            item_json(&mut buf, item.borrow(), first)?;
+            out.item(&buf)?;
+            first = false;
+        }
+
+        let tail = serde_json::to_vec(&FeedbackTail {
+            rerun: &self.rerun,
+            variables: &self.variables,
+            skip_knowledge: &self.skip_knowledge,
+            cache: &self.cache,
+        })?;
+        // strip the braces of tail object: {"rerun":1.0} => ],"rerun":1.0}
+        let mut end = b"]".to_vec();
+        if tail.len() > 2 {
+            end.push(b',');
+            end.extend_from_slice(&tail[1..tail.len() - 1]);
+        }
+        end.push(b'}');
+        out.end(&end)
     }
 
     pub fn add_item(mut self, item: WorkflowItem) -> AlfredWorkflow {
@@ -33,6 +218,47 @@ impl AlfredWorkflow {
         self
     }
 
+    pub(crate) fn take_items(&mut self) -> Vec<WorkflowItem> {
+        std::mem::take(&mut self.items)
+    }
+
+    // ask alfred to rerun the script filter after `seconds`(0.1 ~ 5.0)
+    pub fn rerun(mut self, seconds: f32) -> Result<AlfredWorkflow> {
+        if !(RERUN_MIN_SECONDS..=RERUN_MAX_SECONDS).contains(&seconds) {
+            return Err(WorkflowError::Invalid(
+                "rerun should be between 0.1 and 5.0 seconds".to_string(),
+            ));
+        }
+        self.rerun = Some(seconds);
+        Ok(self)
+    }
+
+    // session variables passed out of the script filter
+    // item/modifier variables with the same name take precedence
+    pub fn vars(mut self, name: &str, value: &str) -> AlfredWorkflow {
+        let mut vars = self.variables.unwrap_or_default();
+        vars.insert(name.to_string(), value.to_string());
+        self.variables = Some(vars);
+        self
+    }
+
+    // keep the items order as given, alfred won't learn from the usage
+    pub fn skip_knowledge(mut self, skip: bool) -> AlfredWorkflow {
+        self.skip_knowledge = Some(skip);
+        self
+    }
+
+    // ask alfred to cache the results, ignored when alfred is older than 5.5
+    pub fn cache_policy(mut self, policy: CachePolicy) -> AlfredWorkflow {
+        let alfred_version = self.get_version();
+        if is_cache_supported(alfred_version.as_str()) {
+            self.cache = Some(policy);
+        } else {
+            log::warn!("alfred {} does not support script filter cache", alfred_version);
+        }
+        self
+    }
+
     // pub fn add_item2(&mut self, item: WorkflowItem) {
     //     let vec = &mut self.items;
     //     vec.push(item);
@@ -43,15 +269,26 @@ impl AlfredWorkflow {
             .subtitle(detail)
             .icon(BuiltinIcon::ERROR.get_icon());
         self.items = vec![error];
-        self.send_feedback();
+        if let Err(e) = self.send_feedback() {
+            log::error!("failed to send fatal error feedback: {}", e);
+        }
         exit(0)
     }
 
     pub fn get_config(&self, name: &str) -> String {
-        std::env::var(name).unwrap_or_default()
+        self.context().var(name)
     }
 }
 
+fn item_json(buf: &mut Vec<u8>, item: &WorkflowItem, first: bool) -> Result<()> {
+    buf.clear();
+    if !first {
+        buf.push(b',');
+    }
+    serde_json::to_writer(buf, item)?;
+    Ok(())
+}
+
 impl AlfredEnv for AlfredWorkflow {
     fn get_preference_path(&self) -> String {
         self.alfred.get_preference_path()
@@ -89,6 +326,14 @@ impl AlfredEnv for AlfredWorkflow {
         self.alfred.get_workflow_name()
     }
 
+    fn get_workflow_description(&self) -> String {
+        self.alfred.get_workflow_description()
+    }
+
+    fn get_workflow_keyword(&self) -> String {
+        self.alfred.get_workflow_keyword()
+    }
+
     fn is_debug_mode(&self) -> bool {
         self.alfred.is_debug_mode()
     }
@@ -112,4 +357,301 @@ impl AlfredEnv for AlfredWorkflow {
     fn get_theme_subtext(&self) -> String {
         self.alfred.get_theme_subtext()
     }
+
+    fn is_running_in_alfred(&self) -> bool {
+        self.alfred.is_running_in_alfred()
+    }
+}
+
+#[cfg(test)]
+mod workflow_feedback_test {
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use crate::alfred::AlfredEnv;
+    use crate::alfred_context::AlfredContext;
+    use crate::workflow::{is_cache_supported, AlfredWorkflow, CachePolicy};
+    use crate::workflow_item::WorkflowItem;
+    use crate::workflow_panic::{FeedbackOutput, Sink};
+
+    fn workflow() -> AlfredWorkflow {
+        AlfredWorkflow::from_context(AlfredContext::default())
+    }
+
+    // the feedback output captured instead of printed
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>, Arc<AtomicUsize>);
+
+    impl Sink for Captured {
+        fn write_with<R>(&self, f: impl FnOnce(&mut dyn Write) -> R) -> R {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            f(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl Captured {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_send_twice_without_hook() {
+        let captured = Captured::default();
+        let output = FeedbackOutput::new(captured.clone());
+        let wf = workflow().add_item(WorkflowItem::new("t"));
+        wf.send_to(&output).unwrap();
+        wf.send_iter_to(&output, Vec::<WorkflowItem>::new())
+            .unwrap();
+
+        let doc = r#"{"items":[{"title":"t","valid":true}]}"#;
+        assert_eq!(captured.text(), format!("{}{}", doc, doc));
+    }
+
+    #[test]
+    fn test_worker_panic_while_streaming() {
+        let captured = Captured::default();
+        let output = FeedbackOutput::new(captured.clone());
+        output.hook();
+
+        let wf = workflow().add_item(WorkflowItem::new("head"));
+        let items = (0..5).map(|i| {
+            if i == 2 {
+                // the hook runs on the worker, while the stream is not done
+                std::thread::scope(|s| {
+                    let worker = s.spawn(|| {
+                        output.report_panic("boom", "worker.rs:1");
+                        panic!("boom")
+                    });
+                    assert!(worker.join().is_err());
+                });
+            }
+            WorkflowItem::new(format!("n{}", i).as_str())
+        });
+        wf.send_iter_to(&output, items).unwrap();
+        // nothing is written after the reported panic
+        wf.send_to(&output).unwrap();
+
+        let v: serde_json::Value = serde_json::from_str(captured.text().as_str()).unwrap();
+        let titles: Vec<&str> = v["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|it| it["title"].as_str().unwrap())
+            .collect();
+        assert_eq!(titles, vec!["head", "n0", "n1", "Workflow panicked"]);
+    }
+
+    #[test]
+    fn test_hooked_stream_written_by_batches() {
+        let captured = Captured::default();
+        let output = FeedbackOutput::new(captured.clone());
+        output.hook();
+
+        let items = (0..1000).map(|i| WorkflowItem::new(format!("n{}", i).as_str()));
+        workflow().send_iter_to(&output, items).unwrap();
+        let v: serde_json::Value = serde_json::from_str(captured.text().as_str()).unwrap();
+        assert_eq!(v["items"].as_array().unwrap().len(), 1000);
+        assert!(captured.1.load(Ordering::SeqCst) < 10);
+
+        // a panic after some batches are written completes the rest
+        let captured = Captured::default();
+        let output = FeedbackOutput::new(captured.clone());
+        output.hook();
+        let items = (0..1000).map(|i| {
+            if i == 700 {
+                output.report_panic("boom", "main.rs:1");
+            }
+            WorkflowItem::new(format!("n{}", i).as_str())
+        });
+        workflow().send_iter_to(&output, items).unwrap();
+        let v: serde_json::Value = serde_json::from_str(captured.text().as_str()).unwrap();
+        let items = v["items"].as_array().unwrap();
+        assert_eq!(items.len(), 701);
+        assert_eq!(items[699]["title"], "n699");
+        assert_eq!(items[700]["title"], "Workflow panicked");
+    }
+
+    #[test]
+    fn test_feedback_only_items() {
+        let wf = workflow().add_item(WorkflowItem::new("t"));
+        assert_eq!(
+            serde_json::to_string(&wf).unwrap(),
+            r#"{"items":[{"title":"t","valid":true}]}"#
+        );
+    }
+
+    #[test]
+    fn test_feedback_with_top_level_fields() {
+        let wf = workflow()
+            .rerun(0.5)
+            .unwrap()
+            .vars("session", "abc")
+            .skip_knowledge(true);
+        assert_eq!(
+            serde_json::to_string(&wf).unwrap(),
+            r#"{"items":[],"rerun":0.5,"variables":{"session":"abc"},"skipknowledge":true}"#
+        );
+    }
+
+    #[test]
+    fn test_rerun_out_of_range() {
+        assert!(workflow().rerun(0.0).is_err());
+        assert!(workflow().rerun(5.1).is_err());
+        assert!(workflow().rerun(0.1).is_ok());
+        assert!(workflow().rerun(5.0).is_ok());
+    }
+
+    #[test]
+    fn test_feedback_with_cache() {
+        let mut wf = workflow();
+        wf.cache = Some(CachePolicy::new(60).unwrap().loose_reload(true));
+        assert_eq!(
+            serde_json::to_string(&wf).unwrap(),
+            r#"{"items":[],"cache":{"seconds":60,"loosereload":true}}"#
+        );
+
+        wf.cache = Some(CachePolicy::new(5).unwrap());
+        assert_eq!(
+            serde_json::to_string(&wf).unwrap(),
+            r#"{"items":[],"cache":{"seconds":5}}"#
+        );
+    }
+
+    #[test]
+    fn test_cache_policy_by_alfred_version() {
+        let of_alfred = |version: &str| {
+            let vars = [("alfred_version".to_string(), version.to_string())].into_iter().collect();
+            AlfredWorkflow::from_context(AlfredContext::from_map(vars))
+        };
+        let policy = || CachePolicy::new(60).unwrap();
+
+        for version in ["5.5", "5.5.1", "6.0"] {
+            let wf = of_alfred(version).cache_policy(policy());
+            assert_eq!(
+                serde_json::to_string(&wf).unwrap(),
+                r#"{"items":[],"cache":{"seconds":60}}"#,
+                "{}",
+                version
+            );
+        }
+
+        for version in ["5.1.4", "4.6", ""] {
+            let wf = of_alfred(version).cache_policy(policy());
+            assert_eq!(serde_json::to_string(&wf).unwrap(), r#"{"items":[]}"#, "{}", version);
+        }
+    }
+
+    #[test]
+    fn test_cache_policy_out_of_range() {
+        assert!(CachePolicy::new(4).is_err());
+        assert!(CachePolicy::new(86401).is_err());
+        assert!(CachePolicy::new(86400).is_ok());
+    }
+
+    #[test]
+    fn test_cache_supported_by_alfred_version() {
+        assert!(is_cache_supported("5.5"));
+        assert!(is_cache_supported("5.5.1"));
+        assert!(is_cache_supported("6.0"));
+        assert!(!is_cache_supported("5.1.4"));
+        assert!(!is_cache_supported("4.6"));
+        assert!(!is_cache_supported(""));
+    }
+
+    #[test]
+    fn test_write_feedback_to_writer() {
+        let wf = workflow()
+            .add_item(WorkflowItem::new("t").subtitle("s"))
+            .vars("k", "v");
+        let mut out: Vec<u8> = Vec::new();
+        wf.write_feedback(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"items":[{"title":"t","subtitle":"s","valid":true}],"variables":{"k":"v"}}"#
+        );
+    }
+
+    #[test]
+    fn test_write_feedback_error_returned() {
+        struct BrokenWriter;
+        impl std::io::Write for BrokenWriter {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "closed",
+                ))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let wf = workflow().add_item(WorkflowItem::new("t"));
+        assert!(wf.write_feedback(BrokenWriter).is_err());
+    }
+
+    #[test]
+    fn test_write_feedback_iter_same_as_write_feedback() {
+        let wf = workflow()
+            .add_item(WorkflowItem::new("a"))
+            .add_item(WorkflowItem::new("b"))
+            .rerun(1.0)
+            .unwrap()
+            .vars("k", "v")
+            .skip_knowledge(false);
+        let mut expect: Vec<u8> = Vec::new();
+        wf.write_feedback(&mut expect).unwrap();
+
+        let mut out: Vec<u8> = Vec::new();
+        wf.write_feedback_iter(&mut out, Vec::<WorkflowItem>::new())
+            .unwrap();
+        assert_eq!(out, expect);
+    }
+
+    #[test]
+    fn test_write_feedback_iter_streams_items() {
+        let wf = workflow().add_item(WorkflowItem::new("head"));
+        let mut out: Vec<u8> = Vec::new();
+        wf.write_feedback_iter(
+            &mut out,
+            (0..3).map(|i| WorkflowItem::new(format!("n{}", i).as_str())),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"items":[{"title":"head","valid":true},{"title":"n0","valid":true},{"title":"n1","valid":true},{"title":"n2","valid":true}]}"#
+        );
+    }
+
+    #[test]
+    fn test_write_feedback_iter_borrowed_items() {
+        let items = [WorkflowItem::new("x"), WorkflowItem::new("y")];
+        let mut out: Vec<u8> = Vec::new();
+        workflow()
+            .vars("k", "v")
+            .write_feedback_iter(&mut out, items.iter())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            r#"{"items":[{"title":"x","valid":true},{"title":"y","valid":true}],"variables":{"k":"v"}}"#
+        );
+    }
+
+    #[test]
+    fn test_workflow_from_context() {
+        let vars = [
+            ("alfred_workflow_bundleid", "com.demo.wf"),
+            ("api_token", "xyz"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let wf = AlfredWorkflow::from_context(AlfredContext::from_map(vars));
+        assert_eq!(wf.get_workflow_bundle_id(), "com.demo.wf");
+        assert_eq!(wf.context().workflow_bundle_id(), Some("com.demo.wf"));
+        assert_eq!(wf.get_config("api_token"), "xyz");
+        assert_eq!(wf.get_workflow_name(), "");
+    }
 }
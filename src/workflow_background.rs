@@ -5,24 +5,25 @@ use std::time::Duration;
 use nix::libc::printf;
 use crate::alfred::AlfredEnv;
 use crate::workflow::AlfredWorkflow;
-use nix::unistd::{fork, ForkResult};
+use crate::workflow_error::Result;
+use nix::unistd::fork;
 
 impl AlfredWorkflow {
-    pub fn run_background(&self, f: &dyn Fn(&AlfredWorkflow) -> ()) {
-        let fork_rs = fork();
-        if fork_rs.is_ok() {
-            let fork = fork_rs.unwrap();
-            if fork.is_child() {
-                f(self)
-            }
+    pub fn run_background(&self, f: &dyn Fn(&AlfredWorkflow)) -> Result<()> {
+        let fork = unsafe { fork() }?;
+        if fork.is_child() {
+            f(self);
+            // the child must not go back to the caller's flow
+            std::process::exit(0)
         }
+        Ok(())
     }
 }
 
 #[test]
 fn test_run_backgroud() {
-    let workflow = AlfredWorkflow::init();
-    workflow.run_background(&cc);
+    let workflow = AlfredWorkflow::init().unwrap();
+    workflow.run_background(&cc).unwrap();
     print!("hello")
 }
 
@@ -30,7 +31,7 @@ fn cc(v: &AlfredWorkflow) {
     let mut file = OpenOptions::new()
         .create(true)
         .write(true)
-        .open("./test.log").unwrap();
+        .open(std::env::temp_dir().join("alfred_background_test.log")).unwrap();
     file.write("cccachjashdckjas".as_bytes());
     print!("ccc => {}", v.get_workflow_cache_path())
 }
\ No newline at end of file
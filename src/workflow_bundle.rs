@@ -0,0 +1,236 @@
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use zip::ZipArchive;
+
+use crate::workflow_error::{Result, WorkflowError};
+use crate::workflow_plist::{WorkflowInfo, INFO_PLIST};
+
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+// a file in the bundle
+#[derive(Clone, Debug, PartialEq)]
+pub struct BundleFile {
+    pub name: String,
+    pub size: u64,
+    // the unix permission bits, if the archive has them
+    pub mode: Option<u32>,
+    pub is_dir: bool,
+}
+
+// an existing `.alfredworkflow`(a zip) to look into or extract
+pub struct WorkflowBundle<R> {
+    zip: ZipArchive<R>,
+}
+
+impl WorkflowBundle<File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<WorkflowBundle<File>> {
+        WorkflowBundle::from_reader(File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> WorkflowBundle<R> {
+    pub fn from_reader(reader: R) -> Result<WorkflowBundle<R>> {
+        Ok(WorkflowBundle {
+            zip: ZipArchive::new(reader)?,
+        })
+    }
+
+    pub fn files(&mut self) -> Result<Vec<BundleFile>> {
+        let mut files = Vec::with_capacity(self.zip.len());
+        for i in 0..self.zip.len() {
+            let file = self.zip.by_index(i)?;
+            files.push(BundleFile {
+                name: file.name().to_string(),
+                size: file.size(),
+                mode: file.unix_mode().map(|m| m & 0o7777),
+                is_dir: file.is_dir(),
+            });
+        }
+        Ok(files)
+    }
+
+    // the metadata of the `info.plist` at the bundle root
+    pub fn info(&mut self) -> Result<WorkflowInfo> {
+        let mut data = Vec::new();
+        self.zip.by_name(INFO_PLIST)?.read_to_end(&mut data)?;
+        WorkflowInfo::from_reader(Cursor::new(data))
+    }
+
+    pub fn version(&mut self) -> Result<Option<String>> {
+        Ok(self.info()?.version)
+    }
+
+    // extract into the folder, the file modes are kept
+    // nothing is written if any entry would land outside the folder
+    // (absolute path, `..`) or is a symlink
+    pub fn unpack<P: AsRef<Path>>(&mut self, dest: P) -> Result<Vec<PathBuf>> {
+        let dest = dest.as_ref();
+        let mut targets = Vec::with_capacity(self.zip.len());
+        for i in 0..self.zip.len() {
+            let file = self.zip.by_index(i)?;
+            if file.unix_mode().is_some_and(|m| m & S_IFMT == S_IFLNK) {
+                return Err(unsafe_entry(file.name(), "symlink"));
+            }
+            targets.push(dest.join(safe_path(file.name())?));
+        }
+
+        for (i, target) in targets.iter().enumerate() {
+            let mut file = self.zip.by_index(i)?;
+            if file.is_dir() {
+                std::fs::create_dir_all(target)?;
+                continue;
+            }
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut out = File::create(target)?;
+            std::io::copy(&mut file, &mut out)?;
+            if let Some(mode) = file.unix_mode() {
+                std::fs::set_permissions(target, PermissionsExt::from_mode(mode & 0o777))?;
+            }
+        }
+        Ok(targets)
+    }
+}
+
+// the entry name as a relative path with plain components only
+fn safe_path(name: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir => return Err(unsafe_entry(name, "parent folder")),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(unsafe_entry(name, "absolute path"))
+            }
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(unsafe_entry(name, "empty path"));
+    }
+    Ok(path)
+}
+
+fn unsafe_entry(name: &str, reason: &str) -> WorkflowError {
+    WorkflowError::Invalid(format!("unsafe entry {:?} in bundle: {}", name, reason))
+}
+
+#[cfg(test)]
+mod workflow_bundle_test {
+    use std::io::{Cursor, Write};
+    use std::os::unix::fs::PermissionsExt;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use crate::test_util::TempDir;
+    use crate::workflow_bundle::WorkflowBundle;
+    use crate::workflow_definition::WorkflowDefinition;
+
+    // a bundle with the given (name, mode, content) entries
+    fn archive(entries: &[(&str, u32, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, mode, data) in entries {
+            let options = FileOptions::default().unix_permissions(*mode);
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        let mut cursor = zip.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    fn info_plist() -> Vec<u8> {
+        let mut xml = Vec::new();
+        WorkflowDefinition::new("com.demo.bundle", "Bundle")
+            .version("2.0.1")
+            .write_xml(&mut xml)
+            .unwrap();
+        xml
+    }
+
+    #[test]
+    fn test_inspect() {
+        let plist = info_plist();
+        let mut bundle = WorkflowBundle::from_reader(archive(&[
+            ("info.plist", 0o644, &plist),
+            ("run", 0o755, b"#!/bin/sh"),
+        ]))
+        .unwrap();
+
+        let files = bundle.files().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1].name, "run");
+        assert_eq!(files[1].size, 9);
+        assert_eq!(files[1].mode, Some(0o755));
+
+        let info = bundle.info().unwrap();
+        assert_eq!(info.bundle_id.as_deref(), Some("com.demo.bundle"));
+        assert_eq!(bundle.version().unwrap().as_deref(), Some("2.0.1"));
+    }
+
+    #[test]
+    fn test_inspect_without_plist() {
+        let mut bundle = WorkflowBundle::from_reader(archive(&[("run", 0o755, b"")])).unwrap();
+        assert!(bundle.info().is_err());
+        assert!(WorkflowBundle::from_reader(Cursor::new(b"not a zip".to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_unpack() {
+        let plist = info_plist();
+        let temp = TempDir::new("bundle_unpack");
+        let dir = temp.join("out");
+        let mut bundle = WorkflowBundle::from_reader(archive(&[
+            ("info.plist", 0o644, &plist),
+            ("run", 0o755, b"#!/bin/sh"),
+            ("./icons/star.png", 0o644, b"png"),
+        ]))
+        .unwrap();
+
+        let written = bundle.unpack(&dir).unwrap();
+        assert_eq!(written[2], dir.join("icons/star.png"));
+        assert_eq!(std::fs::read(dir.join("icons/star.png")).unwrap(), b"png");
+        let mode = dir.join("run").metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn test_unpack_rejects_traversal() {
+        for name in ["../evil", "icons/../../evil", "/tmp/evil"] {
+            let temp = TempDir::new("bundle_traversal");
+            let dir = temp.join("out");
+            let mut bundle = WorkflowBundle::from_reader(archive(&[
+                ("info.plist", 0o644, b"<plist/>"),
+                (name, 0o644, b"evil"),
+            ]))
+            .unwrap();
+
+            let err = bundle.unpack(&dir).err().unwrap();
+            assert!(err.to_string().contains("unsafe entry"), "{}", name);
+            // nothing is extracted, not even the safe entries before it
+            assert!(!dir.exists(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_unpack_rejects_symlink() {
+        let temp = TempDir::new("bundle_symlink");
+        let dir = temp.join("out");
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.add_symlink("link", "/etc/passwd", FileOptions::default())
+            .unwrap();
+        let mut cursor = zip.finish().unwrap();
+        cursor.set_position(0);
+
+        let mut bundle = WorkflowBundle::from_reader(cursor).unwrap();
+        let err = bundle.unpack(&dir).err().unwrap();
+        assert!(err.to_string().contains("symlink"));
+        assert!(!dir.exists());
+    }
+}
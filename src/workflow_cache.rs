@@ -1,73 +1,87 @@
-use std::error::Error;
-use std::fs::OpenOptions;
+use std::fs::{DirBuilder, OpenOptions};
 use std::io::{Read, Write};
-use std::os::unix::raw::pthread_t;
-use std::path::Path;
-use std::time::{Duration, SystemTime};
-use crate::alfred::AlfredEnv;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::alfred_context::AlfredContext;
 use crate::workflow::AlfredWorkflow;
+use crate::workflow_error::{Result, WorkflowError};
+
+// where alfred puts the folders when the env var is not set
+const CACHE_ROOT: &str = "Library/Caches/com.runningwithcrayons.Alfred/Workflow Data";
+const DATA_ROOT: &str = "Library/Application Support/Alfred/Workflow Data";
+
+// the folders are private to the user
+const DIR_MODE: u32 = 0o700;
+
+// the folder from the env var, or `$HOME/<root>/<bundleid>` as alfred lays it out
+fn resolve_dir(ctx: &AlfredContext, dir: Option<&Path>, root: &str) -> Result<PathBuf> {
+    if let Some(dir) = dir {
+        return Ok(dir.to_path_buf());
+    }
+    let bundle_id = ctx.workflow_bundle_id().ok_or_else(|| {
+        WorkflowError::Invalid("alfred_workflow_bundleid is not set".to_string())
+    })?;
+    let home = ctx.var("HOME");
+    if home.is_empty() {
+        return Err(WorkflowError::Invalid("HOME is not set".to_string()));
+    }
+    Ok(Path::new(&home).join(root).join(bundle_id))
+}
+
+// alfred does not create the folders, so create them on first use
+fn ensure_dir(dir: PathBuf) -> Result<PathBuf> {
+    if !dir.is_dir() {
+        DirBuilder::new().recursive(true).mode(DIR_MODE).create(&dir)?;
+    }
+    Ok(dir)
+}
 
 impl AlfredWorkflow {
-    pub fn cache(&mut self, name: &str, data: &str) {
-        let pre_path = self.get_workflow_cache_path();
-        let full_name = format!("{}/{}", pre_path, name);
+    // the workflow cache folder, created if missing
+    pub fn cache_dir(&self) -> Result<PathBuf> {
+        let ctx = self.context();
+        ensure_dir(resolve_dir(ctx, ctx.workflow_cache(), CACHE_ROOT)?)
+    }
+
+    // the workflow data folder, created if missing
+    pub fn data_dir(&self) -> Result<PathBuf> {
+        let ctx = self.context();
+        ensure_dir(resolve_dir(ctx, ctx.workflow_data(), DATA_ROOT)?)
+    }
 
-        // try open cache file
-        let file_rs = OpenOptions::new()
+    pub fn cache(&mut self, name: &str, data: &str) -> Result<()> {
+        let full_name = self.cache_dir()?.join(name);
+
+        // try open cache file and write data to it
+        let mut file = OpenOptions::new()
             .create(true)
             .write(true)
-            .open(full_name);
-
-        // if open failed
-        // report fatal error
-        if file_rs.is_err() {
-            self.fatal_error("CacheError", file_rs.err().unwrap().description());
-        } else {
-            // write data to cache file
-            let mut file = file_rs.unwrap();
-            let write_rs = file.write(data.as_bytes());
-            if write_rs.is_err() {
-                self.fatal_error("CacheError", write_rs.err().unwrap().description())
-            }
-        }
+            .truncate(true)
+            .open(full_name)?;
+        file.write_all(data.as_bytes())?;
+        Ok(())
     }
 
-    pub fn load(&mut self, name: &str) -> String {
-        let pre_path = self.get_workflow_cache_path();
-        let full_name = format!("{}/{}", pre_path, name);
+    pub fn load(&mut self, name: &str) -> Result<String> {
+        let full_name = self.cache_dir()?.join(name);
 
-        let file_rs = OpenOptions::new()
+        // read data from cache file
+        let mut file = OpenOptions::new()
             .read(true)
-            .open(full_name);
-
-        if file_rs.is_err() {
-            self.fatal_error("CacheError", file_rs.err().unwrap().description());
-            "".to_string()
-        } else {
-            // read data from cache file
-            let mut file = file_rs.unwrap();
-            let mut buf = String::new();
-            let read_rs = file.read_to_string(&mut buf);
-            if read_rs.is_err() {
-                self.fatal_error("CacheError", read_rs.err().unwrap().description());
-            }
-            buf
-        }
+            .open(full_name)?;
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+        Ok(buf)
     }
 
-    pub fn expired(&mut self, name: &str, max_age: u64) -> bool {
-        let pre_path = self.get_workflow_cache_path();
-        let full_name = format!("{}/{}", pre_path, name);
-
-        let result = Path::new(full_name.as_str()).metadata();
-        if result.is_err() {
-            self.fatal_error("CacheError", result.err().unwrap().description());
-            return false;
-        } else {
-            let modified = result.unwrap().modified().unwrap();
-            let elapsed = modified.elapsed().unwrap().as_secs();
-            return elapsed > max_age;
-        }
+    pub fn expired(&mut self, name: &str, max_age: u64) -> Result<bool> {
+        let full_name = self.cache_dir()?.join(name);
+
+        let modified = full_name.metadata()?.modified()?;
+        // modified in the future(clock changed) is treated as just modified
+        let elapsed = modified.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+        Ok(elapsed > max_age)
     }
 }
 
@@ -82,14 +96,16 @@ fn test_is_file_exist() {
 
     let result = Path::new("/Users/christfm/Downloads/gradle-icon2.jpf").metadata();
     print!("{:?}", result);
-    let result = Path::new("./test").metadata();
+    // in the temp folder, so running the tests leaves nothing in the repo
+    let path = std::env::temp_dir().join("alfred_is_file_exist_test");
+    let result = path.metadata();
     print!("{:?}", result);
 
     let mut file = OpenOptions::new()
         .create(true)
         .write(true)
         .append(true)
-        .open("./test")
+        .open(&path)
         .unwrap();
 
     file.write("ccc".as_bytes());
@@ -102,17 +118,85 @@ fn test_is_file_exist() {
 fn test_workflow_cache() {
     dotenv::dotenv().ok();
 
-    let mut workflow = AlfredWorkflow::init();
-    workflow.cache("test", "just_cache");
+    // the .env cache folder is the working folder, use the temp folder instead
+    let cache = crate::test_util::TempDir::new("workflow_cache");
+    let mut vars = AlfredContext::from_env().vars().clone();
+    vars.insert("alfred_workflow_cache".to_string(), cache.to_string_lossy().to_string());
+    let mut workflow = AlfredWorkflow::from_context(AlfredContext::from_map(vars));
+    workflow.cache("test", "just_cache").unwrap();
 
-    let is_expired = workflow.expired("test", 0);
+    let is_expired = workflow.expired("test", 0).unwrap();
     assert_eq!(is_expired, false);
 
-    let content = workflow.load("test");
+    let content = workflow.load("test").unwrap();
     assert_eq!(content, "just_cache".to_string());
 
     std::thread::sleep(Duration::new(1, 0));
 
-    let is_expired = workflow.expired("test", 0);
+    let is_expired = workflow.expired("test", 0).unwrap();
     assert_eq!(is_expired, true);
 }
+
+#[cfg(test)]
+mod workflow_cache_test {
+    use std::collections::HashMap;
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::alfred_context::AlfredContext;
+    use crate::test_util::TempDir;
+    use crate::workflow::AlfredWorkflow;
+
+    fn workflow(vars: &[(&str, &str)]) -> AlfredWorkflow {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        AlfredWorkflow::from_context(AlfredContext::from_map(map))
+    }
+
+    #[test]
+    fn test_dirs_from_env_created() {
+        let home = TempDir::new("cache_env");
+        let cache = home.join("cache");
+        let data = home.join("data");
+        let mut wf = workflow(&[
+            ("alfred_workflow_cache", cache.to_str().unwrap()),
+            ("alfred_workflow_data", data.to_str().unwrap()),
+        ]);
+
+        assert_eq!(wf.cache_dir().unwrap(), cache);
+        assert_eq!(wf.data_dir().unwrap(), data);
+        let mode = cache.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert!(data.is_dir());
+
+        wf.cache("first", "write").unwrap();
+        assert_eq!(wf.load("first").unwrap(), "write");
+    }
+
+    #[test]
+    fn test_dirs_fallback_to_alfred_layout() {
+        let home = TempDir::new("cache_fallback");
+        let wf = workflow(&[
+            ("HOME", home.to_str().unwrap()),
+            ("alfred_workflow_bundleid", "com.demo.wf"),
+            ("alfred_workflow_cache", ""),
+        ]);
+
+        assert_eq!(
+            wf.cache_dir().unwrap(),
+            home.join("Library/Caches/com.runningwithcrayons.Alfred/Workflow Data/com.demo.wf")
+        );
+        assert_eq!(
+            wf.data_dir().unwrap(),
+            home.join("Library/Application Support/Alfred/Workflow Data/com.demo.wf")
+        );
+    }
+
+    #[test]
+    fn test_dirs_without_bundle_id() {
+        let wf = workflow(&[("HOME", "/tmp")]);
+        assert!(wf.cache_dir().is_err());
+        assert!(wf.data_dir().is_err());
+    }
+}
@@ -1 +1,550 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 
+use serde::de::value::SeqDeserializer;
+use serde::de::{DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
+use serde::{forward_to_deserialize_any, Deserialize, Deserializer};
+
+use crate::workflow::AlfredWorkflow;
+use crate::workflow_error::{Result, WorkflowError};
+
+// a user configuration value that can not be used
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigIssue {
+    pub key: String,
+    pub message: String,
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.key.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.key, self.message)
+        }
+    }
+}
+
+// build the user configuration struct from the workflow variables
+// 1. a field is read from the variable with the same name(use `serde(rename)` for others)
+// 2. empty variables are treated as not set, so `serde(default)` applies
+// 3. numbers and booleans(1/0, true/false, yes/no, on/off) are parsed,
+//    lists are comma separated
+// 4. every bad key is reported at once, not only the first one
+pub fn from_vars<T: DeserializeOwned>(vars: &HashMap<String, String>) -> Result<T> {
+    // serde stops at a missing field or a value it can not take, such a key
+    // gets a placeholder on the next run, so the remaining keys are checked too
+    let mut placeholders = HashSet::new();
+    let mut skipped = Vec::new();
+    loop {
+        let issues = RefCell::new(Vec::new());
+        let result = T::deserialize(VarsDeserializer {
+            vars,
+            placeholders: &placeholders,
+            issues: &issues,
+        });
+
+        let mut issues = issues.into_inner();
+        match result {
+            Ok(config) if issues.is_empty() && skipped.is_empty() => return Ok(config),
+            Ok(_) => {}
+            Err(e) => {
+                let issue = e.into_issue();
+                let retry = !issue.key.is_empty() && placeholders.insert(issue.key.clone());
+                skipped.push(issue);
+                if retry {
+                    continue;
+                }
+            }
+        }
+        issues.extend(skipped);
+        return Err(WorkflowError::Config(issues));
+    }
+}
+
+// `deserialize_with` helper to expand the leading `~` of a path
+pub fn expand_home<'de, D: Deserializer<'de>>(d: D) -> std::result::Result<PathBuf, D::Error> {
+    let path = String::deserialize(d)?;
+    let home = std::env::var("HOME").unwrap_or_default();
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            Ok(PathBuf::from(format!("{}{}", home, rest)))
+        }
+        _ => Ok(PathBuf::from(path)),
+    }
+}
+
+impl AlfredWorkflow {
+    // the typed user configuration, see `from_vars`
+    pub fn config<T: DeserializeOwned>(&self) -> Result<T> {
+        from_vars(self.context().vars())
+    }
+}
+
+#[derive(Debug)]
+struct ConfigError {
+    key: Option<String>,
+    message: String,
+}
+
+impl ConfigError {
+    fn into_issue(self) -> ConfigIssue {
+        ConfigIssue {
+            key: self.key.unwrap_or_default(),
+            message: self.message,
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl serde::de::Error for ConfigError {
+    fn custom<T: Display>(msg: T) -> Self {
+        ConfigError {
+            key: None,
+            message: msg.to_string(),
+        }
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        ConfigError {
+            key: Some(field.to_string()),
+            message: "is required but not set".to_string(),
+        }
+    }
+}
+
+// the whole variable map
+struct VarsDeserializer<'a> {
+    vars: &'a HashMap<String, String>,
+    // keys already reported, they get a value that always deserializes
+    placeholders: &'a HashSet<String>,
+    issues: &'a RefCell<Vec<ConfigIssue>>,
+}
+
+impl<'de, 'a> Deserializer<'de> for VarsDeserializer<'a> {
+    type Error = ConfigError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        let entries = self
+            .vars
+            .iter()
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| (k.as_str(), v.as_str(), false));
+        visitor.visit_map(VarsAccess::new(entries, self.issues))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        let (vars, placeholders) = (self.vars, self.placeholders);
+        let entries = fields.iter().filter_map(move |f| {
+            if placeholders.contains(*f) {
+                return Some((*f, "", true));
+            }
+            vars.get(*f)
+                .filter(|v| !v.is_empty())
+                .map(|v| (*f, v.as_str(), false))
+        });
+        visitor.visit_map(VarsAccess::new(entries, self.issues))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+struct VarsAccess<'a, I> {
+    entries: I,
+    value: Option<(&'a str, &'a str, bool)>,
+    issues: &'a RefCell<Vec<ConfigIssue>>,
+}
+
+impl<'a, I> VarsAccess<'a, I> {
+    fn new(entries: I, issues: &'a RefCell<Vec<ConfigIssue>>) -> Self {
+        VarsAccess {
+            entries,
+            value: None,
+            issues,
+        }
+    }
+}
+
+impl<'de, 'a, I> MapAccess<'de> for VarsAccess<'a, I>
+where
+    I: Iterator<Item = (&'a str, &'a str, bool)>,
+{
+    type Error = ConfigError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, ConfigError> {
+        match self.entries.next() {
+            Some((key, value, placeholder)) => {
+                self.value = Some((key, value, placeholder));
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        let (key, raw, placeholder) = self
+            .value
+            .take()
+            .ok_or_else(|| serde::de::Error::custom("value is missing"))?;
+        seed.deserialize(ValueDeserializer {
+            key,
+            raw,
+            placeholder,
+            issues: self.issues,
+        })
+        .map_err(|mut e| {
+            e.key.get_or_insert_with(|| key.to_string());
+            e
+        })
+    }
+}
+
+// a single variable value
+struct ValueDeserializer<'a> {
+    key: &'a str,
+    raw: &'a str,
+    // empty and never reported, for a key that already has an issue
+    placeholder: bool,
+    issues: &'a RefCell<Vec<ConfigIssue>>,
+}
+
+impl<'a> ValueDeserializer<'a> {
+    // keep going with the parsed value or a placeholder, so the following
+    // keys are checked too
+    fn parse<T: std::str::FromStr + Default>(&self, expected: &str) -> T {
+        if self.placeholder {
+            return T::default();
+        }
+        self.raw.trim().parse().unwrap_or_else(|_| {
+            self.issues.borrow_mut().push(ConfigIssue {
+                key: self.key.to_string(),
+                message: format!("expected {}, got {:?}", expected, self.raw),
+            });
+            T::default()
+        })
+    }
+
+    fn parse_bool(&self) -> bool {
+        match self.raw.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => true,
+            "0" | "false" | "no" | "off" => false,
+            _ if self.placeholder => false,
+            _ => {
+                self.issues.borrow_mut().push(ConfigIssue {
+                    key: self.key.to_string(),
+                    message: format!("expected a boolean, got {:?}", self.raw),
+                });
+                false
+            }
+        }
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident: $ty:ty,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, ConfigError> {
+                visitor.$visit(self.parse::<$ty>(concat!("a number(", stringify!($ty), ")")))
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for ValueDeserializer<'a> {
+    type Error = ConfigError;
+
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        visitor.visit_str(self.raw)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        visitor.visit_bool(self.parse_bool())
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        if self.raw.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // comma separated list, blank entries are dropped
+    fn deserialize_seq<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        let (key, issues) = (self.key, self.issues);
+        let values = self
+            .raw
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|raw| ValueDeserializer {
+                key,
+                raw,
+                placeholder: false,
+                issues,
+            });
+        let mut seq = SeqDeserializer::new(values);
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    // only unit variants, named by the value
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        match variants.first() {
+            Some(first) if self.placeholder => visitor.visit_enum(first.into_deserializer()),
+            _ => visitor.visit_enum(self.raw.trim().into_deserializer()),
+        }
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(
+        self,
+        _visitor: V,
+    ) -> std::result::Result<V::Value, ConfigError> {
+        Err(serde::de::Error::custom("nested maps are not supported"))
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct
+        tuple tuple_struct struct identifier ignored_any
+    }
+}
+
+impl<'de, 'a> IntoDeserializer<'de, ConfigError> for ValueDeserializer<'a> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod workflow_config_test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use serde::Deserialize;
+
+    use crate::alfred_context::AlfredContext;
+    use crate::workflow::AlfredWorkflow;
+    use crate::workflow_config::{expand_home, from_vars, ConfigIssue};
+    use crate::workflow_error::WorkflowError;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Browser {
+        Chrome,
+        Safari,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Config {
+        api_token: String,
+        #[serde(default = "default_limit")]
+        limit: u32,
+        ratio: f64,
+        #[serde(default)]
+        verbose: bool,
+        #[serde(default)]
+        tags: Vec<String>,
+        ports: Option<Vec<u16>>,
+        browser: Browser,
+        #[serde(default, deserialize_with = "expand_home")]
+        download_dir: PathBuf,
+        proxy: Option<String>,
+    }
+
+    fn default_limit() -> u32 {
+        20
+    }
+
+    fn vars(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_config_typed_values() {
+        let config: Config = from_vars(&vars(&[
+            ("api_token", "xyz"),
+            ("limit", ""),
+            ("ratio", " 0.5 "),
+            ("verbose", "1"),
+            ("tags", "rust, alfred,,"),
+            ("ports", "80,443"),
+            ("browser", "safari"),
+            ("download_dir", "/tmp/dl"),
+            ("alfred_version", "5.0"),
+        ]))
+        .unwrap();
+
+        assert_eq!(config.api_token, "xyz");
+        assert_eq!(config.limit, 20);
+        assert_eq!(config.ratio, 0.5);
+        assert!(config.verbose);
+        assert_eq!(config.tags, vec!["rust", "alfred"]);
+        assert_eq!(config.ports, Some(vec![80, 443]));
+        assert_eq!(config.browser, Browser::Safari);
+        assert_eq!(config.download_dir, PathBuf::from("/tmp/dl"));
+        assert_eq!(config.proxy, None);
+    }
+
+    #[test]
+    fn test_config_errors_aggregated() {
+        let result: crate::workflow_error::Result<Config> = from_vars(&vars(&[
+            ("limit", "many"),
+            ("ratio", "0.5"),
+            ("verbose", "maybe"),
+            ("ports", "80,http"),
+            ("browser", "chrome"),
+        ]));
+
+        match result {
+            Err(WorkflowError::Config(issues)) => {
+                let keys: Vec<&str> = issues.iter().map(|i| i.key.as_str()).collect();
+                assert_eq!(keys, vec!["limit", "verbose", "ports", "api_token"]);
+                assert_eq!(
+                    issues[0],
+                    ConfigIssue {
+                        key: "limit".to_string(),
+                        message: r#"expected a number(u32), got "many""#.to_string(),
+                    }
+                );
+                assert_eq!(issues[3].to_string(), "api_token: is required but not set");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_config_bad_enum() {
+        let result: crate::workflow_error::Result<Config> = from_vars(&vars(&[
+            ("api_token", "xyz"),
+            ("ratio", "1"),
+            ("browser", "firefox"),
+        ]));
+        let err = result.err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("browser: unknown variant `firefox`"));
+    }
+
+    #[test]
+    fn test_config_all_issues_reported() {
+        // serde itself gives up at the first missing field or bad value
+        let result: crate::workflow_error::Result<Config> =
+            from_vars(&vars(&[("ratio", "half"), ("limit", "5")]));
+        let err = result.err().unwrap().to_string();
+        assert!(err.contains(r#"ratio: expected a number(f64), got "half""#));
+        assert!(err.contains("api_token: is required but not set"));
+        assert!(err.contains("browser: is required but not set"));
+
+        let result: crate::workflow_error::Result<Config> = from_vars(&vars(&[
+            ("api_token", "xyz"),
+            ("browser", "firefox"),
+            ("ports", "80,http"),
+        ]));
+        let err = result.err().unwrap().to_string();
+        assert!(err.contains("browser: unknown variant `firefox`"));
+        assert!(err.contains("ports: expected a number(u16)"));
+        assert!(err.contains("ratio: is required but not set"));
+
+        // the placeholder of a missing enum lets the other keys be checked
+        let result: crate::workflow_error::Result<Config> =
+            from_vars(&vars(&[("verbose", "maybe")]));
+        match result {
+            Err(WorkflowError::Config(issues)) => {
+                let keys: Vec<&str> = issues.iter().map(|i| i.key.as_str()).collect();
+                assert_eq!(keys, vec!["verbose", "api_token", "ratio", "browser"]);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_config_into_map() {
+        let map: HashMap<String, String> = from_vars(&vars(&[("a", "1"), ("b", "")])).unwrap();
+        assert_eq!(map.len(), 1);
+        assert_eq!(map["a"], "1");
+    }
+
+    #[test]
+    fn test_workflow_config() {
+        #[derive(Deserialize)]
+        struct Simple {
+            #[serde(rename = "PAGE_SIZE")]
+            page_size: usize,
+        }
+
+        let ctx = AlfredContext::from_map(vars(&[("PAGE_SIZE", "9")]));
+        let wf = AlfredWorkflow::from_context(ctx);
+        assert_eq!(wf.config::<Simple>().unwrap().page_size, 9);
+    }
+}
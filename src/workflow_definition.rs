@@ -0,0 +1,643 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+use plist::{Dictionary, Value};
+
+use crate::workflow_error::{Result, WorkflowError};
+use crate::workflow_item::ModKey;
+use crate::workflow_plist::{
+    Connection, UserConfigField, WorkflowInfo, WorkflowObject, SCRIPT_FILTER,
+};
+
+pub const RUN_SCRIPT: &str = "alfred.workflow.action.script";
+pub const OPEN_URL: &str = "alfred.workflow.action.openurl";
+
+// alfred's script `type` for "External Script", the file is run directly
+const EXTERNAL_SCRIPT: i64 = 8;
+// the query is passed as argv
+const ARG_AS_ARGV: i64 = 1;
+// escape spaces, backquotes, double quotes, backslashes and dollars
+const ESCAPING: i64 = 102;
+
+// how the script filter takes the text after the keyword
+pub enum ArgumentMode {
+    REQUIRED,
+    OPTIONAL,
+    NONE,
+}
+
+impl ArgumentMode {
+    fn value(&self) -> i64 {
+        match self {
+            ArgumentMode::REQUIRED => 0,
+            ArgumentMode::OPTIONAL => 1,
+            ArgumentMode::NONE => 2,
+        }
+    }
+}
+
+// the modifier mask alfred stores for the connections
+fn modifier_mask(keys: &[ModKey]) -> u64 {
+    keys.iter()
+        .map(|key| match key {
+            ModKey::SHIFT => 1 << 17,
+            ModKey::CTRL => 1 << 18,
+            ModKey::ALT => 1 << 19,
+            ModKey::CMD => 1 << 20,
+            ModKey::FN => 1 << 23,
+        })
+        .fold(0, |mask, key| mask | key)
+}
+
+// describe the workflow in rust and write its `info.plist`
+// the uid of an object could be any unique text, it's used by the connections
+pub struct WorkflowDefinition {
+    info: WorkflowInfo,
+}
+
+impl WorkflowDefinition {
+    pub fn new(bundle_id: &str, name: &str) -> WorkflowDefinition {
+        WorkflowDefinition {
+            info: WorkflowInfo {
+                bundle_id: Some(bundle_id.to_string()),
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn version(mut self, version: &str) -> WorkflowDefinition {
+        self.info.version = Some(version.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> WorkflowDefinition {
+        self.info.description = Some(description.to_string());
+        self
+    }
+
+    pub fn created_by(mut self, author: &str) -> WorkflowDefinition {
+        self.info.created_by = Some(author.to_string());
+        self
+    }
+
+    pub fn web_address(mut self, url: &str) -> WorkflowDefinition {
+        self.info.web_address = Some(url.to_string());
+        self
+    }
+
+    pub fn variable(mut self, name: &str, value: &str) -> WorkflowDefinition {
+        self.info.variables.retain(|(k, _)| k != name);
+        self.info
+            .variables
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // the variable value is not kept when the workflow is exported
+    pub fn dont_export(mut self, name: &str) -> WorkflowDefinition {
+        if !self.info.variables_dont_export.iter().any(|v| v == name) {
+            self.info.variables_dont_export.push(name.to_string());
+        }
+        self
+    }
+
+    pub fn user_config(mut self, field: UserConfigField) -> WorkflowDefinition {
+        self.info.user_configuration.push(field);
+        self
+    }
+
+    pub fn object<T: Into<WorkflowObject>>(mut self, object: T) -> WorkflowDefinition {
+        self.info.objects.push(object.into());
+        self
+    }
+
+    pub fn connect(self, from: &str, to: &str) -> WorkflowDefinition {
+        self.connection(Connection::new(from, to))
+    }
+
+    pub fn connection(mut self, connection: Connection) -> WorkflowDefinition {
+        self.info.connections.push(connection);
+        self
+    }
+
+    // the definition as the parser would read it back
+    pub fn info(&self) -> &WorkflowInfo {
+        &self.info
+    }
+
+    pub fn to_value(&self) -> Result<Value> {
+        self.validate()?;
+        let info = &self.info;
+
+        // keys sorted as alfred writes them
+        let mut root = Dictionary::new();
+        insert_text(&mut root, "bundleid", &info.bundle_id);
+        root.insert(
+            "connections".to_string(),
+            connections(&info.connections).into(),
+        );
+        insert_text(&mut root, "createdby", &info.created_by);
+        insert_text(&mut root, "description", &info.description);
+        root.insert("disabled".to_string(), false.into());
+        insert_text(&mut root, "name", &info.name);
+        root.insert(
+            "objects".to_string(),
+            info.objects
+                .iter()
+                .map(object)
+                .collect::<Vec<Value>>()
+                .into(),
+        );
+        root.insert("readme".to_string(), "".into());
+        root.insert("uidata".to_string(), uidata(&info.objects).into());
+        root.insert(
+            "userconfigurationconfig".to_string(),
+            info.user_configuration
+                .iter()
+                .map(user_config_field)
+                .collect::<Vec<Value>>()
+                .into(),
+        );
+        let variables: Dictionary = info
+            .variables
+            .iter()
+            .map(|(k, v)| (k.clone(), Value::from(v.as_str())))
+            .collect();
+        root.insert("variables".to_string(), variables.into());
+        root.insert(
+            "variablesdontexport".to_string(),
+            info.variables_dont_export
+                .iter()
+                .map(|v| Value::from(v.as_str()))
+                .collect::<Vec<Value>>()
+                .into(),
+        );
+        insert_text(&mut root, "version", &info.version);
+        insert_text(&mut root, "webaddress", &info.web_address);
+        Ok(root.into())
+    }
+
+    pub fn write_xml<W: Write>(&self, w: W) -> Result<()> {
+        self.to_value()?.to_writer_xml(w)?;
+        Ok(())
+    }
+
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.to_value()?.to_file_xml(path)?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        let info = &self.info;
+        if info.bundle_id.as_deref().unwrap_or_default().is_empty() {
+            return Err(WorkflowError::Invalid("bundle id is empty".to_string()));
+        }
+
+        let mut uids = HashSet::new();
+        for object in &info.objects {
+            if object.uid.is_empty() || !uids.insert(object.uid.as_str()) {
+                return Err(WorkflowError::Invalid(format!(
+                    "object uid {:?} is empty or not unique",
+                    object.uid
+                )));
+            }
+        }
+        for c in &info.connections {
+            for uid in [&c.from, &c.to] {
+                if !uids.contains(uid.as_str()) {
+                    return Err(WorkflowError::Invalid(format!(
+                        "connection to unknown object {:?}",
+                        uid
+                    )));
+                }
+            }
+        }
+        for name in &info.variables_dont_export {
+            if info.variables.iter().all(|(k, _)| k != name) {
+                return Err(WorkflowError::Invalid(format!(
+                    "dont export unknown variable {:?}",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// the script filter object, the script is a file in the workflow folder
+pub struct ScriptFilter {
+    uid: String,
+    config: Dictionary,
+}
+
+impl ScriptFilter {
+    pub fn new(uid: &str, keyword: &str, script_file: &str) -> ScriptFilter {
+        let mut config = Dictionary::new();
+        config.insert("alfredfiltersresults".to_string(), false.into());
+        config.insert("argumenttreatemptyqueryasnil".to_string(), true.into());
+        config.insert(
+            "argumenttype".to_string(),
+            ArgumentMode::OPTIONAL.value().into(),
+        );
+        config.insert("escaping".to_string(), ESCAPING.into());
+        config.insert("keyword".to_string(), keyword.into());
+        config.insert("queuedelaycustom".to_string(), 3.into());
+        config.insert("queuedelayimmediatelyinitially".to_string(), true.into());
+        config.insert("queuedelaymode".to_string(), 0.into());
+        config.insert("queuemode".to_string(), 1.into());
+        config.insert("script".to_string(), "".into());
+        config.insert("scriptargtype".to_string(), ARG_AS_ARGV.into());
+        config.insert("scriptfile".to_string(), script_file.into());
+        config.insert("type".to_string(), EXTERNAL_SCRIPT.into());
+        config.insert("withspace".to_string(), true.into());
+        ScriptFilter {
+            uid: uid.to_string(),
+            config,
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> ScriptFilter {
+        self.config.insert("title".to_string(), title.into());
+        self
+    }
+
+    pub fn subtext(mut self, subtext: &str) -> ScriptFilter {
+        self.config.insert("subtext".to_string(), subtext.into());
+        self
+    }
+
+    // shown while the script is running
+    pub fn running_subtext(mut self, subtext: &str) -> ScriptFilter {
+        self.config
+            .insert("runningsubtext".to_string(), subtext.into());
+        self
+    }
+
+    pub fn argument(mut self, mode: ArgumentMode) -> ScriptFilter {
+        self.config
+            .insert("argumenttype".to_string(), mode.value().into());
+        self
+    }
+
+    // whether a space is needed between the keyword and the query
+    pub fn with_space(mut self, with_space: bool) -> ScriptFilter {
+        self.config
+            .insert("withspace".to_string(), with_space.into());
+        self
+    }
+
+    // let alfred filter the items by the query, the script is run only once
+    pub fn alfred_filters_results(mut self, filters: bool) -> ScriptFilter {
+        self.config
+            .insert("alfredfiltersresults".to_string(), filters.into());
+        self
+    }
+}
+
+impl From<ScriptFilter> for WorkflowObject {
+    fn from(filter: ScriptFilter) -> Self {
+        WorkflowObject {
+            uid: filter.uid,
+            kind: SCRIPT_FILTER.to_string(),
+            version: Some(3),
+            config: filter.config,
+        }
+    }
+}
+
+// the run script action, the script is a file in the workflow folder
+pub struct RunScript {
+    uid: String,
+    config: Dictionary,
+}
+
+impl RunScript {
+    pub fn new(uid: &str, script_file: &str) -> RunScript {
+        let mut config = Dictionary::new();
+        config.insert("concurrently".to_string(), false.into());
+        config.insert("escaping".to_string(), ESCAPING.into());
+        config.insert("script".to_string(), "".into());
+        config.insert("scriptargtype".to_string(), ARG_AS_ARGV.into());
+        config.insert("scriptfile".to_string(), script_file.into());
+        config.insert("type".to_string(), EXTERNAL_SCRIPT.into());
+        RunScript {
+            uid: uid.to_string(),
+            config,
+        }
+    }
+
+    // run a new instance even if the last one is still running
+    pub fn concurrently(mut self, concurrently: bool) -> RunScript {
+        self.config
+            .insert("concurrently".to_string(), concurrently.into());
+        self
+    }
+}
+
+impl From<RunScript> for WorkflowObject {
+    fn from(script: RunScript) -> Self {
+        WorkflowObject {
+            uid: script.uid,
+            kind: RUN_SCRIPT.to_string(),
+            version: Some(2),
+            config: script.config,
+        }
+    }
+}
+
+// open the url, `{query}` is replaced by the input
+pub struct OpenUrl {
+    uid: String,
+    config: Dictionary,
+}
+
+impl OpenUrl {
+    pub fn new(uid: &str, url: &str) -> OpenUrl {
+        let mut config = Dictionary::new();
+        config.insert("browser".to_string(), "".into());
+        config.insert("spaces".to_string(), "".into());
+        config.insert("url".to_string(), url.into());
+        config.insert("utf8".to_string(), true.into());
+        OpenUrl {
+            uid: uid.to_string(),
+            config,
+        }
+    }
+
+    // the bundle id of the browser, the default browser if not set
+    pub fn browser(mut self, browser: &str) -> OpenUrl {
+        self.config.insert("browser".to_string(), browser.into());
+        self
+    }
+}
+
+impl From<OpenUrl> for WorkflowObject {
+    fn from(open: OpenUrl) -> Self {
+        WorkflowObject {
+            uid: open.uid,
+            kind: OPEN_URL.to_string(),
+            version: Some(1),
+            config: open.config,
+        }
+    }
+}
+
+impl Connection {
+    pub fn new(from: &str, to: &str) -> Connection {
+        Connection {
+            from: from.to_string(),
+            to: to.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // the connection is used when these keys are held
+    pub fn modifiers(mut self, keys: Vec<ModKey>) -> Connection {
+        self.modifiers = modifier_mask(&keys);
+        self
+    }
+
+    pub fn subtext(mut self, subtext: &str) -> Connection {
+        self.modifier_subtext = Some(subtext.to_string());
+        self
+    }
+
+    pub fn vito_close(mut self, vito_close: bool) -> Connection {
+        self.vito_close = vito_close;
+        self
+    }
+}
+
+impl UserConfigField {
+    pub fn new(kind: &str, variable: &str, label: &str) -> UserConfigField {
+        UserConfigField {
+            variable: variable.to_string(),
+            kind: kind.to_string(),
+            label: Some(label.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn textfield(variable: &str, label: &str) -> UserConfigField {
+        UserConfigField::new("textfield", variable, label)
+    }
+
+    pub fn textarea(variable: &str, label: &str) -> UserConfigField {
+        UserConfigField::new("textarea", variable, label)
+    }
+
+    pub fn checkbox(variable: &str, label: &str) -> UserConfigField {
+        UserConfigField::new("checkbox", variable, label)
+    }
+
+    pub fn filepicker(variable: &str, label: &str) -> UserConfigField {
+        UserConfigField::new("filepicker", variable, label)
+    }
+
+    pub fn description(mut self, description: &str) -> UserConfigField {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    // as the variable would be set, `1` or `0` for a checkbox
+    pub fn default_value(mut self, value: &str) -> UserConfigField {
+        self.default = Some(value.to_string());
+        self
+    }
+
+    pub fn placeholder(mut self, placeholder: &str) -> UserConfigField {
+        self.placeholder = Some(placeholder.to_string());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> UserConfigField {
+        self.required = required;
+        self
+    }
+}
+
+fn insert_text(dict: &mut Dictionary, key: &str, value: &Option<String>) {
+    if let Some(value) = value {
+        dict.insert(key.to_string(), value.as_str().into());
+    }
+}
+
+fn object(object: &WorkflowObject) -> Value {
+    let mut dict = Dictionary::new();
+    dict.insert("config".to_string(), object.config.clone().into());
+    dict.insert("type".to_string(), object.kind.as_str().into());
+    dict.insert("uid".to_string(), object.uid.as_str().into());
+    if let Some(version) = object.version {
+        dict.insert("version".to_string(), version.into());
+    }
+    dict.into()
+}
+
+// connections are grouped by the source object
+fn connections(connections: &[Connection]) -> Dictionary {
+    let mut dict = Dictionary::new();
+    for c in connections {
+        let mut wire = Dictionary::new();
+        wire.insert("destinationuid".to_string(), c.to.as_str().into());
+        wire.insert("modifiers".to_string(), c.modifiers.into());
+        let subtext = c.modifier_subtext.as_deref().unwrap_or_default();
+        wire.insert("modifiersubtext".to_string(), subtext.into());
+        wire.insert("vitoclose".to_string(), c.vito_close.into());
+
+        if dict.get(&c.from).and_then(Value::as_array).is_none() {
+            dict.insert(c.from.clone(), Value::Array(Vec::new()));
+        }
+        if let Some(wires) = dict.get_mut(&c.from).and_then(Value::as_array_mut) {
+            wires.push(wire.into());
+        }
+    }
+    dict
+}
+
+// objects laid out in a row, so they don't overlap on the canvas
+fn uidata(objects: &[WorkflowObject]) -> Dictionary {
+    objects
+        .iter()
+        .enumerate()
+        .map(|(i, o)| {
+            let mut pos = Dictionary::new();
+            pos.insert("xpos".to_string(), (30 + 200 * i as u64).into());
+            pos.insert("ypos".to_string(), 30.into());
+            (o.uid.clone(), Value::Dictionary(pos))
+        })
+        .collect()
+}
+
+fn user_config_field(field: &UserConfigField) -> Value {
+    let checkbox = field.kind == "checkbox";
+    let mut config = Dictionary::new();
+    let default = field.default.as_deref().unwrap_or_default();
+    if checkbox {
+        config.insert("default".to_string(), (default == "1").into());
+        let label = field.label.as_deref().unwrap_or_default();
+        config.insert("text".to_string(), label.into());
+    } else {
+        config.insert("default".to_string(), default.into());
+        let placeholder = field.placeholder.as_deref().unwrap_or_default();
+        config.insert("placeholder".to_string(), placeholder.into());
+        config.insert("trim".to_string(), true.into());
+    }
+    config.insert("required".to_string(), field.required.into());
+
+    let mut dict = Dictionary::new();
+    dict.insert("config".to_string(), config.into());
+    let description = field.description.as_deref().unwrap_or_default();
+    dict.insert("description".to_string(), description.into());
+    let label = field.label.as_deref().unwrap_or_default();
+    dict.insert("label".to_string(), label.into());
+    dict.insert("type".to_string(), field.kind.as_str().into());
+    dict.insert("variable".to_string(), field.variable.as_str().into());
+    dict.into()
+}
+
+#[cfg(test)]
+mod workflow_definition_test {
+    use std::io::Cursor;
+
+    use crate::test_util::TempDir;
+    use crate::workflow_definition::{
+        ArgumentMode, OpenUrl, RunScript, ScriptFilter, WorkflowDefinition, OPEN_URL,
+    };
+    use crate::workflow_item::ModKey;
+    use crate::workflow_plist::{Connection, UserConfigField, WorkflowInfo};
+
+    fn definition() -> WorkflowDefinition {
+        WorkflowDefinition::new("com.demo.github", "GitHub")
+            .version("1.2.0")
+            .description("Search github repositories")
+            .created_by("christmic")
+            .web_address("https://github.com/chrimst")
+            .variable("page_size", "20")
+            .variable("secret", "hidden")
+            .dont_export("secret")
+            .user_config(
+                UserConfigField::textfield("api_token", "API Token")
+                    .description("Personal access token")
+                    .placeholder("ghp_xxx")
+                    .required(true),
+            )
+            .user_config(UserConfigField::checkbox("include_forks", "Forks").default_value("1"))
+            .object(
+                ScriptFilter::new("search", "gh", "github")
+                    .title("Search GitHub")
+                    .argument(ArgumentMode::REQUIRED),
+            )
+            .object(OpenUrl::new("open", "{query}"))
+            .object(RunScript::new("copy", "copy.sh"))
+            .connect("search", "open")
+            .connection(
+                Connection::new("search", "copy")
+                    .modifiers(vec![ModKey::CMD, ModKey::SHIFT])
+                    .subtext("Copy the url"),
+            )
+    }
+
+    fn round_trip(def: &WorkflowDefinition) -> WorkflowInfo {
+        let mut xml = Vec::new();
+        def.write_xml(&mut xml).unwrap();
+        WorkflowInfo::from_reader(Cursor::new(xml)).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let def = definition();
+        let info = round_trip(&def);
+        assert_eq!(&info, def.info());
+
+        assert_eq!(info.keywords(), vec!["gh"]);
+        assert_eq!(info.object("open").unwrap().kind, OPEN_URL);
+        let wires: Vec<_> = info.connections_from("search").collect();
+        assert_eq!(wires[1].modifiers, 1048576 | 131072);
+        assert_eq!(info.user_configuration[1].default.as_deref(), Some("1"));
+        assert_eq!(info.variables_dont_export, vec!["secret"]);
+    }
+
+    #[test]
+    fn test_xml_output() {
+        let mut xml = Vec::new();
+        definition().write_xml(&mut xml).unwrap();
+        let xml = String::from_utf8(xml).unwrap();
+        assert!(xml.starts_with("<?xml"));
+        assert!(xml.contains("<key>bundleid</key>"));
+        assert!(xml.contains("<string>alfred.workflow.input.scriptfilter</string>"));
+        // root keys are sorted as alfred writes them
+        let bundle = xml.find("<key>bundleid</key>").unwrap();
+        let objects = xml.find("<key>objects</key>").unwrap();
+        let version = xml.rfind("<key>version</key>").unwrap();
+        assert!(bundle < objects && objects < version);
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        let dup = WorkflowDefinition::new("com.demo", "Demo")
+            .object(OpenUrl::new("a", "x"))
+            .object(OpenUrl::new("a", "y"));
+        assert!(dup.to_value().is_err());
+
+        let unknown = WorkflowDefinition::new("com.demo", "Demo")
+            .object(OpenUrl::new("a", "x"))
+            .connect("a", "b");
+        assert!(unknown.to_value().is_err());
+
+        let no_var = WorkflowDefinition::new("com.demo", "Demo").dont_export("token");
+        assert!(no_var.to_value().is_err());
+
+        assert!(WorkflowDefinition::new("", "Demo").to_value().is_err());
+    }
+
+    #[test]
+    fn test_write_file() {
+        let dir = TempDir::new("definition");
+        let path = dir.join("info.plist");
+        definition().write_file(&path).unwrap();
+        let info = WorkflowInfo::from_file(&path).unwrap();
+        assert_eq!(info.bundle_id.as_deref(), Some("com.demo.github"));
+    }
+}
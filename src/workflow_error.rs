@@ -0,0 +1,295 @@
+use std::fmt::{Display, Formatter};
+
+use crate::alfred_logger::Logger;
+use crate::alfred_script::ScriptError;
+use crate::icon::BuiltinIcon;
+use crate::workflow::AlfredWorkflow;
+use crate::workflow_config::ConfigIssue;
+use crate::workflow_item::{ItemText, ItemType, WorkflowItem};
+
+// errors could be raised by the workflow apis
+#[derive(Debug)]
+pub enum WorkflowError {
+    // file(cache, log etc.) or process failure
+    Io(std::io::Error),
+    // http request failure
+    Http(reqwest::Error),
+    // feedback or data (de)serializing failure
+    Json(serde_json::Error),
+    // sqlite database failure
+    Sqlite(sqlite::Error),
+    // macos keychain failure, kept as message as the security framework
+    // error can only be rendered on macos
+    Keychain(String),
+    // invalid url
+    Url(url::ParseError),
+    // info.plist can not be read or written
+    Plist(plist::Error),
+    // .alfredworkflow archive can not be read or written
+    Zip(zip::result::ZipError),
+    // osascript controlling alfred failed
+    Script(ScriptError),
+    // invalid version string
+    Version(String),
+    // logger can not be set up
+    Logger(String),
+    // argument out of range or in bad format
+    Invalid(String),
+    // user configuration variables that can not be used, all at once
+    Config(Vec<ConfigIssue>),
+}
+
+pub type Result<T> = std::result::Result<T, WorkflowError>;
+
+impl Display for WorkflowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorkflowError::Io(e) => write!(f, "io error: {}", e),
+            WorkflowError::Http(e) => write!(f, "http error: {}", e),
+            WorkflowError::Json(e) => write!(f, "json error: {}", e),
+            WorkflowError::Sqlite(e) => write!(f, "sqlite error: {}", e),
+            WorkflowError::Keychain(msg) => write!(f, "keychain error: {}", msg),
+            WorkflowError::Url(e) => write!(f, "url error: {}", e),
+            WorkflowError::Plist(e) => write!(f, "plist error: {}", e),
+            WorkflowError::Zip(e) => write!(f, "zip error: {}", e),
+            WorkflowError::Script(e) => write!(f, "script error: {}", e),
+            WorkflowError::Version(msg) => write!(f, "version error: {}", msg),
+            WorkflowError::Logger(msg) => write!(f, "logger error: {}", msg),
+            WorkflowError::Invalid(msg) => write!(f, "invalid argument: {}", msg),
+            WorkflowError::Config(issues) => {
+                let issues: Vec<String> = issues.iter().map(|i| i.to_string()).collect();
+                write!(f, "config error: {}", issues.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkflowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WorkflowError::Io(e) => Some(e),
+            WorkflowError::Http(e) => Some(e),
+            WorkflowError::Json(e) => Some(e),
+            WorkflowError::Sqlite(e) => Some(e),
+            WorkflowError::Url(e) => Some(e),
+            WorkflowError::Plist(e) => Some(e),
+            WorkflowError::Zip(e) => Some(e),
+            WorkflowError::Script(e) => Some(e),
+            WorkflowError::Keychain(_)
+            | WorkflowError::Version(_)
+            | WorkflowError::Logger(_)
+            | WorkflowError::Invalid(_)
+            | WorkflowError::Config(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for WorkflowError {
+    fn from(e: std::io::Error) -> Self {
+        WorkflowError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for WorkflowError {
+    fn from(e: reqwest::Error) -> Self {
+        WorkflowError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for WorkflowError {
+    fn from(e: serde_json::Error) -> Self {
+        WorkflowError::Json(e)
+    }
+}
+
+impl From<sqlite::Error> for WorkflowError {
+    fn from(e: sqlite::Error) -> Self {
+        WorkflowError::Sqlite(e)
+    }
+}
+
+impl From<security_framework::base::Error> for WorkflowError {
+    fn from(e: security_framework::base::Error) -> Self {
+        WorkflowError::Keychain(e.to_string())
+    }
+}
+
+impl From<url::ParseError> for WorkflowError {
+    fn from(e: url::ParseError) -> Self {
+        WorkflowError::Url(e)
+    }
+}
+
+impl From<plist::Error> for WorkflowError {
+    fn from(e: plist::Error) -> Self {
+        WorkflowError::Plist(e)
+    }
+}
+
+impl From<zip::result::ZipError> for WorkflowError {
+    fn from(e: zip::result::ZipError) -> Self {
+        WorkflowError::Zip(e)
+    }
+}
+
+impl From<nix::Error> for WorkflowError {
+    fn from(e: nix::Error) -> Self {
+        WorkflowError::Io(e.into())
+    }
+}
+
+// the error and its causes as feedback items:
+// 1. the headline with the error message
+// 2. one item for every cause in the source chain, a cause already
+//    shown in the message before it is skipped
+// 3. an item to copy(cmd+c) or show(cmd+l) the error details
+// 4. an item passing the log file as arg, so it can be opened
+pub fn error_items(title: &str, err: &dyn std::error::Error) -> Vec<WorkflowItem> {
+    let mut items = vec![WorkflowItem::new(title)
+        .subtitle(err.to_string().as_str())
+        .icon(BuiltinIcon::ERROR.get_icon())
+        .valid(false)];
+
+    let mut details = format!("{}\n{}", title, err);
+    let mut shown = err.to_string();
+    let mut cause = err.source();
+    while let Some(e) = cause {
+        let msg = e.to_string();
+        cause = e.source();
+        // the wrapping errors repeat their source in the message
+        if shown.contains(&msg) {
+            continue;
+        }
+        items.push(
+            WorkflowItem::new(title)
+                .subtitle(format!("caused by: {}", msg).as_str())
+                .icon(BuiltinIcon::WARNING.get_icon())
+                .valid(false),
+        );
+        details.push_str(format!("\ncaused by: {}", msg).as_str());
+        shown = msg;
+    }
+
+    items.push(
+        WorkflowItem::new("Copy error details")
+            .subtitle("press cmd+c to copy or cmd+l to show the details")
+            .icon(BuiltinIcon::NOTE.get_icon())
+            .args(details.as_str())
+            .text(ItemText::new(details.as_str()).large_text(details.as_str())),
+    );
+
+    if let Ok(log_path) = Logger::log_path() {
+        items.push(
+            WorkflowItem::new("Open log file")
+                .subtitle(log_path.as_str())
+                .icon(BuiltinIcon::INFO.get_icon())
+                .item_type(ItemType::FILE)
+                .args(log_path.as_str()),
+        );
+    }
+    items
+}
+
+impl AlfredWorkflow {
+    // replace all items by the error items, the process keeps running
+    // and the items are shown after `send_feedback`
+    pub fn present_error(mut self, title: &str, err: &dyn std::error::Error) -> AlfredWorkflow {
+        log::error!("{}: {}", title, err);
+        self.take_items();
+        error_items(title, err)
+            .into_iter()
+            .fold(self, |wf, item| wf.add_item(item))
+    }
+
+    // show the error items and exit the process
+    pub fn exit_with_error(self, title: &str, err: &dyn std::error::Error) -> ! {
+        let wf = self.present_error(title, err);
+        if let Err(e) = wf.send_feedback() {
+            log::error!("failed to send error feedback: {}", e);
+        }
+        std::process::exit(0)
+    }
+}
+
+#[cfg(test)]
+mod workflow_error_test {
+    use std::error::Error;
+
+    use crate::workflow_error::{error_items, WorkflowError};
+
+    #[test]
+    fn test_error_display() {
+        let e = WorkflowError::Version("bad".to_string());
+        assert_eq!(e.to_string(), "version error: bad");
+        assert!(e.source().is_none());
+    }
+
+    #[test]
+    fn test_error_from_io() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let e: WorkflowError = io.into();
+        assert_eq!(e.to_string(), "io error: no such file");
+        assert_eq!(e.source().unwrap().to_string(), "no such file");
+    }
+
+    #[test]
+    fn test_error_items_with_cause_chain() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let e: WorkflowError = io.into();
+        let items = error_items("Cache not found", &e);
+
+        let json: Vec<String> = items
+            .iter()
+            .map(|it| serde_json::to_string(it).unwrap())
+            .collect();
+        assert!(json[0].contains(r#""title":"Cache not found""#));
+        assert!(json[0].contains(r#""subtitle":"io error: no such file""#));
+        assert!(json[0].contains("AlertStopIcon.icns"));
+        assert!(json[0].contains(r#""valid":false"#));
+        // the io error is already in the headline, no cause item repeats it
+        assert!(json.iter().all(|it| !it.contains("caused by")));
+        assert!(json[1].contains(r#""copy":"Cache not found\nio error: no such file""#));
+        assert!(json[2].contains(r#""type":"file""#));
+        assert!(json[2].contains("ali_workflow.log"));
+        assert_eq!(items.len(), 3);
+    }
+
+    #[derive(Debug)]
+    struct Outer(std::io::Error);
+
+    impl std::fmt::Display for Outer {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "failed to load the cache")
+        }
+    }
+
+    impl Error for Outer {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn test_error_items_with_new_cause() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let items = error_items("Cache not found", &Outer(io));
+        let json: Vec<String> = items
+            .iter()
+            .map(|it| serde_json::to_string(it).unwrap())
+            .collect();
+        assert!(json[1].contains(r#""subtitle":"caused by: no such file""#));
+        assert!(json[2].contains(
+            r#""copy":"Cache not found\nfailed to load the cache\ncaused by: no such file""#
+        ));
+        // headline, cause, copy details, open log
+        assert_eq!(items.len(), 4);
+    }
+
+    #[test]
+    fn test_error_items_without_cause() {
+        let e = WorkflowError::Invalid("bad".to_string());
+        let items = error_items("Oops", &e);
+        // headline, copy details, open log
+        assert_eq!(items.len(), 3);
+    }
+}
@@ -0,0 +1,398 @@
+use crate::workflow::AlfredWorkflow;
+use crate::workflow_item::WorkflowItem;
+use crate::workflow_normalize;
+use crate::workflow_pinyin;
+
+// the way a query is matched against the item text
+// strategies are tried in the given order, the highest score wins
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MatchStrategy {
+    // query equals the text
+    EXACT,
+    // text starts with the query
+    PREFIX,
+    // query matches the first letters of words, eg: `gc` => `Google Chrome`
+    INITIALS,
+    // query is contained in the text
+    SUBSTRING,
+    // query chars appear in the text in order, eg: `sfr` => `Safari`
+    FUZZY,
+}
+
+impl MatchStrategy {
+    // score range of the strategy, a better strategy always ranks higher
+    fn max_score(&self) -> f64 {
+        match self {
+            MatchStrategy::EXACT => 100.0,
+            MatchStrategy::PREFIX => 90.0,
+            MatchStrategy::INITIALS => 80.0,
+            MatchStrategy::SUBSTRING => 70.0,
+            MatchStrategy::FUZZY => 60.0,
+        }
+    }
+
+    // `initials` are the word initials of the raw text, folded like `text`
+    fn score(&self, query: &str, text: &str, initials: &str) -> Option<f64> {
+        let max = self.max_score();
+        match self {
+            MatchStrategy::EXACT => (query == text).then_some(max),
+            MatchStrategy::PREFIX => text
+                .starts_with(query)
+                .then(|| max - 9.0 * (1.0 - ratio(query, text))),
+            MatchStrategy::INITIALS => {
+                if initials.starts_with(query) {
+                    Some(max - 4.0 * (1.0 - ratio(query, initials)))
+                } else if initials.contains(query) {
+                    Some(max - 9.0)
+                } else {
+                    None
+                }
+            }
+            MatchStrategy::SUBSTRING => text.find(query).map(|pos| {
+                let offset = text[..pos].chars().count() as f64;
+                let len = text.chars().count() as f64;
+                max - 9.0 * (offset / len)
+            }),
+            MatchStrategy::FUZZY => subsequence_span(query, text).map(|(start, span)| {
+                let compact = query.chars().count() as f64 / span as f64;
+                let head = if start == 0 { 1.0 } else { 0.0 };
+                max - 20.0 * (1.0 - compact) - 5.0 * (1.0 - head)
+            }),
+        }
+    }
+}
+
+// the char length ratio of query in text
+fn ratio(query: &str, text: &str) -> f64 {
+    query.chars().count() as f64 / text.chars().count().max(1) as f64
+}
+
+// first letters of every word, words split by separators and camel case
+// the case is kept, it is needed to find the camel case words
+fn word_initials(text: &str) -> String {
+    let mut initials = String::new();
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        let is_word_head = match prev {
+            None => c.is_alphanumeric(),
+            Some(p) => {
+                c.is_alphanumeric()
+                    && (!p.is_alphanumeric() || (p.is_lowercase() && c.is_uppercase()))
+            }
+        };
+        if is_word_head {
+            initials.push(c);
+        }
+        prev = Some(c);
+    }
+    initials
+}
+
+// find query chars in text in order
+// return the char index of the first matched char and the matched span length
+fn subsequence_span(query: &str, text: &str) -> Option<(usize, usize)> {
+    let mut query_chars = query.chars().peekable();
+    let mut start = None;
+    let mut end = 0;
+    for (idx, c) in text.chars().enumerate() {
+        match query_chars.peek() {
+            Some(q) if *q == c => {
+                if start.is_none() {
+                    start = Some(idx);
+                }
+                end = idx;
+                query_chars.next();
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+    if query_chars.peek().is_some() {
+        return None;
+    }
+    start.map(|s| (s, end - s + 1))
+}
+
+// score and rank workflow items by the user query
+// the item `match` field is used when set, otherwise the `title`
+pub struct ItemFilter {
+    strategies: Vec<MatchStrategy>,
+    min_score: f64,
+    max_results: Option<usize>,
+    pinyin: bool,
+    normalize: bool,
+}
+
+impl ItemFilter {
+    pub fn new() -> ItemFilter {
+        ItemFilter {
+            strategies: vec![
+                MatchStrategy::EXACT,
+                MatchStrategy::PREFIX,
+                MatchStrategy::INITIALS,
+                MatchStrategy::SUBSTRING,
+                MatchStrategy::FUZZY,
+            ],
+            min_score: 0.0,
+            max_results: None,
+            pinyin: false,
+            normalize: true,
+        }
+    }
+
+    pub fn strategies(mut self, strategies: Vec<MatchStrategy>) -> ItemFilter {
+        self.strategies = strategies;
+        self
+    }
+
+    // drop the items scored lower than `score`(0 ~ 100)
+    pub fn min_score(mut self, score: f64) -> ItemFilter {
+        self.min_score = score;
+        self
+    }
+
+    pub fn max_results(mut self, max: usize) -> ItemFilter {
+        self.max_results = Some(max);
+        self
+    }
+
+    // also match han chars by their pinyin and initials, eg: `wx` => 微信
+    pub fn pinyin(mut self, enable: bool) -> ItemFilter {
+        self.pinyin = enable;
+        self
+    }
+
+    // fold case, diacritics and full-width chars of both query and text
+    // before matching(on by default), disable it to match the raw text
+    pub fn normalize(mut self, enable: bool) -> ItemFilter {
+        self.normalize = enable;
+        self
+    }
+
+    fn prepare(&self, text: &str) -> String {
+        if self.normalize {
+            workflow_normalize::fold(text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    // the best score of all strategies, None if nothing matched
+    pub fn score(&self, query: &str, text: &str) -> Option<f64> {
+        let query = self.prepare(query.trim());
+        if query.is_empty() {
+            return None;
+        }
+
+        // the initials come from the raw text, folding first loses the camel case
+        let mut texts = vec![(self.prepare(text), self.prepare(&word_initials(text)))];
+        if self.pinyin {
+            texts.extend(workflow_pinyin::match_texts(text).into_iter().map(|t| {
+                let initials = word_initials(&t);
+                (t, initials)
+            }));
+        }
+        texts
+            .iter()
+            .flat_map(|(t, initials)| {
+                self.strategies
+                    .iter()
+                    .filter_map(|s| s.score(query.as_str(), t.as_str(), initials.as_str()))
+            })
+            .fold(None, |best: Option<f64>, score| match best {
+                Some(b) if b >= score => Some(b),
+                _ => Some(score),
+            })
+            .filter(|score| *score >= self.min_score)
+    }
+
+    // the matched items with scores, best first
+    // items with equal scores keep their original order
+    pub fn rank(&self, query: &str, items: Vec<WorkflowItem>) -> Vec<(WorkflowItem, f64)> {
+        let mut scored: Vec<(WorkflowItem, f64)> = items
+            .into_iter()
+            .filter_map(|item| {
+                let score = self.score(query, item.match_text());
+                score.map(|s| (item, s))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        if let Some(max) = self.max_results {
+            scored.truncate(max);
+        }
+        scored
+    }
+
+    // the matched items, best first
+    pub fn filter(&self, query: &str, items: Vec<WorkflowItem>) -> Vec<WorkflowItem> {
+        self.rank(query, items)
+            .into_iter()
+            .map(|(item, _)| item)
+            .collect()
+    }
+}
+
+impl Default for ItemFilter {
+    fn default() -> Self {
+        ItemFilter::new()
+    }
+}
+
+impl AlfredWorkflow {
+    // keep only the items matching the query, best first
+    // all items are kept(and cut by max results) when the query is empty
+    pub fn filter(mut self, query: &str, filter: &ItemFilter) -> AlfredWorkflow {
+        let items = self.take_items();
+        let items = if query.trim().is_empty() {
+            let mut items = items;
+            if let Some(max) = filter.max_results {
+                items.truncate(max);
+            }
+            items
+        } else {
+            filter.filter(query, items)
+        };
+        items.into_iter().fold(self, |wf, item| wf.add_item(item))
+    }
+}
+
+#[cfg(test)]
+mod workflow_filter_test {
+    use crate::workflow_filter::{word_initials, ItemFilter, MatchStrategy};
+    use crate::workflow_item::WorkflowItem;
+
+    fn titles(items: Vec<WorkflowItem>) -> Vec<String> {
+        items.iter().map(|it| it.match_text().to_string()).collect()
+    }
+
+    fn apps() -> Vec<WorkflowItem> {
+        vec![
+            WorkflowItem::new("Script Editor"),
+            WorkflowItem::new("Safari"),
+            WorkflowItem::new("Google Chrome"),
+            WorkflowItem::new("Safe Mode"),
+            WorkflowItem::new("Sa"),
+            WorkflowItem::new("Visual Studio Code").matches("vscode Visual Studio Code"),
+        ]
+    }
+
+    #[test]
+    fn test_word_initials() {
+        assert_eq!(word_initials("Google Chrome"), "GC");
+        assert_eq!(word_initials("visual-studio_code"), "vsc");
+        assert_eq!(word_initials("IntelliJ IDEA"), "IJI");
+        assert_eq!(word_initials("  leading space"), "ls");
+    }
+
+    #[test]
+    fn test_single_strategy_score() {
+        assert_eq!(MatchStrategy::EXACT.score("sa", "sa", "s"), Some(100.0));
+        assert_eq!(MatchStrategy::EXACT.score("sa", "safari", "s"), None);
+        assert_eq!(
+            MatchStrategy::PREFIX.score("safari", "safari", "s"),
+            Some(90.0)
+        );
+        assert!(MatchStrategy::INITIALS
+            .score("gc", "google chrome", "gc")
+            .is_some());
+        assert!(MatchStrategy::SUBSTRING
+            .score("fari", "safari", "s")
+            .is_some());
+        assert!(MatchStrategy::FUZZY.score("sfr", "safari", "s").is_some());
+        assert!(MatchStrategy::FUZZY.score("rfs", "safari", "s").is_none());
+    }
+
+    #[test]
+    fn test_initials_of_camel_case() {
+        let initials = ItemFilter::new().strategies(vec![MatchStrategy::INITIALS]);
+        assert!(initials.score("iji", "IntelliJ IDEA").is_some());
+        assert!(initials.score("IJ", "IntelliJ IDEA").is_some());
+        assert!(initials.score("eu", "ÉclairÜber").is_some());
+        let ranked = initials.filter("iji", vec![WorkflowItem::new("IntelliJ IDEA")]);
+        assert_eq!(titles(ranked), vec!["IntelliJ IDEA"]);
+
+        // the raw initials keep their case
+        let raw = initials.normalize(false);
+        assert!(raw.score("IJI", "IntelliJ IDEA").is_some());
+        assert!(raw.score("iji", "IntelliJ IDEA").is_none());
+    }
+
+    #[test]
+    fn test_rank_known_order() {
+        let ranked = ItemFilter::new().filter("sa", apps());
+        assert_eq!(
+            titles(ranked),
+            vec!["Sa", "Safari", "Safe Mode", "vscode Visual Studio Code"]
+        );
+    }
+
+    #[test]
+    fn test_rank_word_initials_before_fuzzy() {
+        let ranked = ItemFilter::new().filter("gc", apps());
+        assert_eq!(titles(ranked), vec!["Google Chrome"]);
+
+        let ranked = ItemFilter::new().filter("se", apps());
+        assert_eq!(
+            titles(ranked),
+            vec!["Script Editor", "Safe Mode", "vscode Visual Studio Code"]
+        );
+    }
+
+    #[test]
+    fn test_rank_use_matches_field() {
+        let ranked = ItemFilter::new().filter("vscode", apps());
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].match_text(), "vscode Visual Studio Code");
+    }
+
+    #[test]
+    fn test_min_score_and_max_results() {
+        let ranked = ItemFilter::new().min_score(80.0).filter("sa", apps());
+        assert_eq!(titles(ranked), vec!["Sa", "Safari", "Safe Mode"]);
+
+        let ranked = ItemFilter::new().max_results(2).filter("sa", apps());
+        assert_eq!(titles(ranked), vec!["Sa", "Safari"]);
+    }
+
+    #[test]
+    fn test_strategies_configurable() {
+        let ranked = ItemFilter::new()
+            .strategies(vec![MatchStrategy::EXACT])
+            .filter("safari", apps());
+        assert_eq!(titles(ranked), vec!["Safari"]);
+
+        let ranked = ItemFilter::new()
+            .strategies(vec![MatchStrategy::PREFIX])
+            .filter("chrome", apps());
+        assert!(ranked.is_empty());
+    }
+
+    #[test]
+    fn test_normalized_matching() {
+        let items = vec![
+            WorkflowItem::new("Café de Flore"),
+            WorkflowItem::new("ＦｕｌｌＷｉｄｔｈ"),
+        ];
+        let ranked = ItemFilter::new().filter("cafe", items);
+        assert_eq!(titles(ranked), vec!["Café de Flore"]);
+
+        let items = vec![WorkflowItem::new("ＦｕｌｌＷｉｄｔｈ")];
+        let ranked = ItemFilter::new().filter("fullw", items);
+        assert_eq!(titles(ranked), vec!["ＦｕｌｌＷｉｄｔｈ"]);
+
+        assert!(ItemFilter::new().score("CAFÉ", "cafe").is_some());
+    }
+
+    #[test]
+    fn test_normalize_disabled() {
+        let filter = ItemFilter::new().normalize(false);
+        assert!(filter.score("cafe", "Café").is_none());
+        assert!(filter.score("saf", "Safari").is_none());
+        assert!(filter.score("Saf", "Safari").is_some());
+    }
+
+    #[test]
+    fn test_empty_query_matches_nothing() {
+        assert!(ItemFilter::new().score("  ", "Safari").is_none());
+    }
+}
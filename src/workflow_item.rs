@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use serde::ser::SerializeSeq;
-use serde::{Deserialize, Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::common::EnumIdent;
 use crate::icon::Icon;
@@ -17,9 +17,15 @@ pub enum ModKey {
 
 #[derive(Serialize, Deserialize)]
 pub struct Modifier {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Option::is_none"
+    )]
     arg: Option<Vec<String>>,
+    #[serde(default)]
     subtitle: String,
+    #[serde(default = "default_valid")]
     valid: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     icon: Option<Icon>,
@@ -66,6 +72,38 @@ impl Modifier {
         self.variables = Some(map);
         self
     }
+
+    pub fn get_subtitle(&self) -> &str {
+        self.subtitle.as_str()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn get_args(&self) -> &[String] {
+        self.arg.as_deref().unwrap_or_default()
+    }
+}
+
+// items and modifiers are actionable unless told otherwise
+fn default_valid() -> bool {
+    true
+}
+
+// alfred takes a single string or a list for `arg` and `text`
+fn one_or_many<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(Option::<OneOrMany>::deserialize(d)?.map(|v| match v {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    }))
 }
 
 pub enum ItemType {
@@ -107,12 +145,17 @@ pub struct WorkflowItem {
     subtitle: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     icon: Option<Icon>,
+    #[serde(default = "default_valid")]
     valid: bool,
     #[serde(rename = "match", skip_serializing_if = "Option::is_none")]
     matches: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     mods: Option<HashMap<String, Modifier>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Option::is_none"
+    )]
     arg: Option<Vec<String>>,
     #[serde(rename = "action", skip_serializing_if = "Option::is_none")]
     actions: Option<Action>,
@@ -219,10 +262,49 @@ impl WorkflowItem {
         self.arg = Some(vec);
         self
     }
+
+    // the text alfred filters by: `match` if set, otherwise `title`
+    pub fn match_text(&self) -> &str {
+        self.matches.as_deref().unwrap_or(self.title.as_str())
+    }
+
+    pub fn get_uid(&self) -> Option<&str> {
+        self.uid.as_deref()
+    }
+
+    pub fn get_title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    pub fn get_subtitle(&self) -> Option<&str> {
+        self.subtitle.as_deref()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn get_args(&self) -> &[String] {
+        self.arg.as_deref().unwrap_or_default()
+    }
+
+    pub fn get_auto_complete(&self) -> Option<&str> {
+        self.autocomplete.as_deref()
+    }
+
+    // keyed by the keys joined with `+`, e.g. `cmd+shift`
+    pub fn get_mods(&self) -> Option<&HashMap<String, Modifier>> {
+        self.mods.as_ref()
+    }
+
+    pub fn get_variables(&self) -> Option<&HashMap<String, String>> {
+        self.variables.as_ref()
+    }
 }
 
 // in later
 #[derive(Deserialize)]
+#[serde(untagged)]
 pub enum Action {
     SingleItem(String),
     MultiItem(Vec<String>),
@@ -250,7 +332,11 @@ impl Serialize for Action {
 
 #[derive(Serialize, Deserialize)]
 pub struct ActionItem {
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Option::is_none"
+    )]
     text: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     url: Option<String>,
@@ -322,3 +408,47 @@ impl ItemText {
         self
     }
 }
+
+#[cfg(test)]
+mod workflow_item_test {
+    use crate::workflow_item::{ModKey, Modifier, WorkflowItem};
+
+    #[test]
+    fn test_item_from_alfred_json() {
+        let json = r#"{
+            "title": "Desktop",
+            "arg": "~/Desktop",
+            "autocomplete": "Desktop",
+            "icon": {"path": "icon.png"},
+            "action": {"text": "copy me", "url": "https://alfred.app"},
+            "mods": {"cmd": {"subtitle": "reveal", "arg": ["a", "b"], "valid": false}}
+        }"#;
+        let item: WorkflowItem = serde_json::from_str(json).unwrap();
+        assert_eq!(item.get_title(), "Desktop");
+        assert!(item.is_valid());
+        assert_eq!(item.get_args(), ["~/Desktop"]);
+        assert_eq!(item.get_auto_complete(), Some("Desktop"));
+        let cmd = &item.get_mods().unwrap()["cmd"];
+        assert_eq!(cmd.get_subtitle(), "reveal");
+        assert_eq!(cmd.get_args(), ["a", "b"]);
+        assert!(!cmd.is_valid());
+
+        // written back as alfred reads it
+        let out = serde_json::to_string(&item).unwrap();
+        assert!(out.contains(r#""action":{"text":["copy me"],"url":"https://alfred.app"}"#));
+    }
+
+    #[test]
+    fn test_item_round_trip() {
+        let item = WorkflowItem::new("Item")
+            .subtitle("sub")
+            .valid(false)
+            .args("x")
+            .mods(vec![ModKey::ALT], Modifier::new().subtitle("alt"));
+        let json = serde_json::to_string(&item).unwrap();
+        let back: WorkflowItem = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+        assert_eq!(back.get_subtitle(), Some("sub"));
+        assert!(!back.is_valid());
+    }
+}
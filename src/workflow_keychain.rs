@@ -1,18 +1,19 @@
 use crate::alfred::AlfredEnv;
 use crate::workflow::AlfredWorkflow;
+use crate::workflow_error::{Result, WorkflowError};
 use security_framework::passwords;
 
 impl AlfredWorkflow {
-    pub fn get_generic_password(&self, name: &str) -> String {
+    pub fn get_generic_password(&self, name: &str) -> Result<String> {
         let service = self.get_workflow_bundle_id();
-        String::from_utf8(
-            passwords::get_generic_password(service.as_str(), name).unwrap_or_default(),
-        )
-        .unwrap_or_default()
+        let password = passwords::get_generic_password(service.as_str(), name)?;
+        String::from_utf8(password)
+            .map_err(|e| WorkflowError::Invalid(format!("password is not utf8: {}", e)))
     }
 
-    pub fn set_generic_password(&self, name: &str, password: &str) -> bool {
+    pub fn set_generic_password(&self, name: &str, password: &str) -> Result<()> {
         let service = self.get_workflow_bundle_id();
-        passwords::set_generic_password(service.as_str(), name, password.as_bytes()).is_ok()
+        passwords::set_generic_password(service.as_str(), name, password.as_bytes())?;
+        Ok(())
     }
 }
@@ -0,0 +1,37 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// fold the text for searching, so `cafe` matches `Café` and `ＡＢＣ` matches `abc`
+// 1. NFKD: compatibility chars(full-width, ligatures) become their plain forms
+//    and accented chars are split into base char + combining marks
+// 2. the combining marks(diacritics) are dropped
+// 3. case folding
+pub fn fold(text: &str) -> String {
+    text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod workflow_normalize_test {
+    use crate::workflow_normalize::fold;
+
+    #[test]
+    fn test_fold_diacritics() {
+        assert_eq!(fold("Café"), "cafe");
+        assert_eq!(fold("Crème Brûlée"), "creme brulee");
+        assert_eq!(fold("Ångström"), "angstrom");
+    }
+
+    #[test]
+    fn test_fold_full_width() {
+        assert_eq!(fold("ＡＢＣ１２３"), "abc123");
+        assert_eq!(fold("ﬁle"), "file");
+    }
+
+    #[test]
+    fn test_fold_keep_han() {
+        assert_eq!(fold("微信 WeChat"), "微信 wechat");
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+use crate::workflow_error::{Result, WorkflowError};
+use crate::workflow_plist::{WorkflowInfo, INFO_PLIST};
+
+pub const EXTENSION: &str = "alfredworkflow";
+pub const ICON: &str = "icon.png";
+
+const EXEC_MODE: u32 = 0o755;
+const FILE_MODE: u32 = 0o644;
+
+// a file to put into the package
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackageEntry {
+    // the path in the package
+    pub name: String,
+    pub source: PathBuf,
+    pub executable: bool,
+}
+
+// bundle the workflow files into an installable `.alfredworkflow`(a zip)
+// 1. every entry gets the same 1980-01-01 timestamp and sorted by name,
+//    so the same input always gives the same archive
+// 2. the executable and the script files of info.plist are 0755, others 0644
+// 3. the manifest is checked before anything is written
+pub struct WorkflowPackage {
+    info_plist: PathBuf,
+    executable: Option<PathBuf>,
+    icon: Option<PathBuf>,
+    assets: Vec<PathBuf>,
+}
+
+impl WorkflowPackage {
+    pub fn new<P: AsRef<Path>>(info_plist: P) -> WorkflowPackage {
+        WorkflowPackage {
+            info_plist: info_plist.as_ref().to_path_buf(),
+            executable: None,
+            icon: None,
+            assets: Vec::new(),
+        }
+    }
+
+    // the built workflow binary, packaged by its file name
+    pub fn executable<P: AsRef<Path>>(mut self, path: P) -> WorkflowPackage {
+        self.executable = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // packaged as `icon.png`
+    pub fn icon<P: AsRef<Path>>(mut self, path: P) -> WorkflowPackage {
+        self.icon = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // a file, or a folder with all the files in it
+    pub fn asset<P: AsRef<Path>>(mut self, path: P) -> WorkflowPackage {
+        self.assets.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    // the checked manifest, see `check`
+    pub fn entries(&self) -> Result<Vec<PackageEntry>> {
+        Ok(self.check()?.1)
+    }
+
+    // the metadata and the entries of the package, fails if
+    // 1. info.plist can not be parsed or misses the bundle id
+    // 2. any file is missing or two files get the same name
+    // 3. a script file used by info.plist is not packaged
+    pub fn check(&self) -> Result<(WorkflowInfo, Vec<PackageEntry>)> {
+        let info = WorkflowInfo::from_file(&self.info_plist)?;
+        if info.bundle_id.is_none() {
+            return Err(invalid("info.plist has no bundle id"));
+        }
+
+        let mut entries = BTreeMap::new();
+        add_entry(
+            &mut entries,
+            INFO_PLIST.to_string(),
+            &self.info_plist,
+            false,
+        )?;
+        if let Some(icon) = &self.icon {
+            add_entry(&mut entries, ICON.to_string(), icon, false)?;
+        }
+        if let Some(exec) = &self.executable {
+            add_entry(&mut entries, file_name(exec)?, exec, true)?;
+        }
+        for asset in &self.assets {
+            if asset.is_dir() {
+                let base = file_name(asset)?;
+                for file in walk(asset)? {
+                    let relative = file.strip_prefix(asset).unwrap_or(&file);
+                    let name = format!("{}/{}", base, relative.to_string_lossy());
+                    add_entry(&mut entries, name, &file, false)?;
+                }
+            } else {
+                add_entry(&mut entries, file_name(asset)?, asset, false)?;
+            }
+        }
+
+        for script in info.objects.iter().filter_map(|o| o.script_file()) {
+            match entries.get_mut(script) {
+                Some(entry) => entry.executable = true,
+                None => return Err(invalid(&format!("script file {} is not packaged", script))),
+            }
+        }
+        Ok((info, entries.into_values().collect()))
+    }
+
+    pub fn write_to<W: Write + Seek>(&self, w: W) -> Result<WorkflowInfo> {
+        let (info, entries) = self.check()?;
+        write_entries(w, entries)?;
+        Ok(info)
+    }
+
+    // `<out_dir>/<name>-<version>.alfredworkflow`, the written path is returned
+    pub fn write_into<P: AsRef<Path>>(&self, out_dir: P) -> Result<PathBuf> {
+        let info = WorkflowInfo::from_file(&self.info_plist)?;
+        let name = info.name.or(info.bundle_id).unwrap_or_default();
+        let name = file_part(&name)
+            .ok_or_else(|| invalid("the workflow has no name to name the package by"))?;
+        let file = match info.version.as_deref().and_then(file_part) {
+            Some(version) => format!("{}-{}.{}", name, version, EXTENSION),
+            None => format!("{}.{}", name, EXTENSION),
+        };
+        let path = out_dir.as_ref().join(file);
+        self.write_file(&path)?;
+        Ok(path)
+    }
+
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<WorkflowInfo> {
+        // check first, so a bad manifest leaves no broken file
+        let (info, entries) = self.check()?;
+        write_entries(File::create(path)?, entries)?;
+        Ok(info)
+    }
+}
+
+fn write_entries<W: Write + Seek>(w: W, entries: Vec<PackageEntry>) -> Result<()> {
+    let mut zip = ZipWriter::new(w);
+    for entry in entries {
+        let mode = if entry.executable {
+            EXEC_MODE
+        } else {
+            FILE_MODE
+        };
+        let options = FileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default())
+            .unix_permissions(mode);
+        zip.start_file(entry.name.as_str(), options)?;
+        std::io::copy(&mut File::open(&entry.source)?, &mut zip)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn invalid(msg: &str) -> WorkflowError {
+    WorkflowError::Invalid(msg.to_string())
+}
+
+// the name or version as part of a single file name, so the package stays
+// in the out dir: separators and blanks become `_`, leading dots are dropped
+fn file_part(raw: &str) -> Option<String> {
+    let part: String = raw
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '_',
+            c if c.is_whitespace() || c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let part = part.trim_start_matches('.');
+    (!part.trim_matches('_').is_empty()).then(|| part.to_string())
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| invalid(&format!("{} has no file name", path.display())))
+}
+
+fn add_entry(
+    entries: &mut BTreeMap<String, PackageEntry>,
+    name: String,
+    source: &Path,
+    executable: bool,
+) -> Result<()> {
+    if !source.is_file() {
+        return Err(invalid(&format!("{} is not a file", source.display())));
+    }
+    if entries.contains_key(&name) {
+        return Err(invalid(&format!("{} is packaged twice", name)));
+    }
+    let entry = PackageEntry {
+        name: name.clone(),
+        source: source.to_path_buf(),
+        executable,
+    };
+    entries.insert(name, entry);
+    Ok(())
+}
+
+// all the files in the folder, hidden ones(.DS_Store etc.) are skipped
+// symlinks are rejected, they may point out of the folder or loop forever
+fn walk(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path
+            .file_name()
+            .is_none_or(|n| n.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            return Err(invalid(&format!("{} is a symlink", path.display())));
+        }
+        if file_type.is_dir() {
+            files.extend(walk(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod workflow_package_test {
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use zip::{DateTime, ZipArchive};
+
+    use crate::test_util::TempDir;
+    use crate::workflow_definition::{ScriptFilter, WorkflowDefinition};
+    use crate::workflow_package::{file_part, WorkflowPackage};
+
+    // a workflow folder with a script filter running `github`
+    fn workflow_dir(test: &str) -> TempDir {
+        let dir = TempDir::new(&format!("package_{}", test));
+        std::fs::create_dir_all(dir.join("assets/icons")).unwrap();
+        WorkflowDefinition::new("com.demo.github", "GitHub")
+            .version("1.2.0")
+            .object(ScriptFilter::new("search", "gh", "github"))
+            .write_file(dir.join("info.plist"))
+            .unwrap();
+        std::fs::write(dir.join("github"), b"binary").unwrap();
+        std::fs::write(dir.join("assets/icons/star.png"), b"png").unwrap();
+        std::fs::write(dir.join("assets/.DS_Store"), b"junk").unwrap();
+        dir
+    }
+
+    fn package(dir: &Path) -> WorkflowPackage {
+        let icon = Path::new(env!("CARGO_MANIFEST_DIR")).join("default_icon.png");
+        WorkflowPackage::new(dir.join("info.plist"))
+            .executable(dir.join("github"))
+            .icon(icon)
+            .asset(dir.join("assets"))
+    }
+
+    fn build(package: &WorkflowPackage) -> Vec<u8> {
+        let mut buf = Cursor::new(Vec::new());
+        package.write_to(&mut buf).unwrap();
+        buf.into_inner()
+    }
+
+    #[test]
+    fn test_package_entries() {
+        let dir = workflow_dir("entries");
+        let bytes = build(&package(&dir));
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let names: Vec<&str> = zip.file_names().collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(
+            sorted,
+            vec!["assets/icons/star.png", "github", "icon.png", "info.plist"]
+        );
+
+        for i in 0..zip.len() {
+            let file = zip.by_index(i).unwrap();
+            let expected = if file.name() == "github" {
+                0o755
+            } else {
+                0o644
+            };
+            assert_eq!(
+                file.unix_mode().unwrap() & 0o777,
+                expected,
+                "{}",
+                file.name()
+            );
+            assert_eq!(
+                file.last_modified().datepart(),
+                DateTime::default().datepart()
+            );
+            assert_eq!(file.last_modified().timepart(), 0);
+        }
+    }
+
+    #[test]
+    fn test_package_reproducible() {
+        let dir = workflow_dir("reproducible");
+        let first = build(&package(&dir));
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        std::fs::write(dir.join("github"), b"binary").unwrap();
+        assert_eq!(first, build(&package(&dir)));
+    }
+
+    #[test]
+    fn test_manifest_check() {
+        let dir = workflow_dir("check");
+        // the script filter runs `github`, which is not packaged
+        let no_exec = WorkflowPackage::new(dir.join("info.plist"));
+        let err = no_exec.check().err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("script file github is not packaged"));
+
+        let missing_icon = package(&dir).icon(dir.join("missing.png"));
+        assert!(missing_icon.check().is_err());
+
+        let twice = package(&dir).asset(dir.join("github"));
+        assert!(twice.check().err().unwrap().to_string().contains("twice"));
+
+        let not_plist = WorkflowPackage::new(dir.join("github"));
+        assert!(not_plist.check().is_err());
+    }
+
+    #[test]
+    fn test_asset_symlink_rejected() {
+        let dir = workflow_dir("symlink");
+        // a link back to the parent would walk forever
+        std::os::unix::fs::symlink(dir.join("assets"), dir.join("assets/icons/loop")).unwrap();
+        let err = package(&dir).check().err().unwrap();
+        assert!(err.to_string().contains("is a symlink"));
+
+        let mut buf = Cursor::new(Vec::new());
+        assert!(package(&dir).write_to(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_package_file_name() {
+        assert_eq!(file_part("My Workflow").as_deref(), Some("My_Workflow"));
+        assert_eq!(file_part("../../etc/x").as_deref(), Some("_.._etc_x"));
+        assert_eq!(file_part("..").as_deref(), None);
+        assert_eq!(file_part(" / ").as_deref(), None);
+        assert_eq!(file_part("").as_deref(), None);
+
+        let dir = workflow_dir("name");
+        let out = dir.join("out");
+        std::fs::create_dir_all(&out).unwrap();
+        WorkflowDefinition::new("com.demo.github", "../../evil")
+            .version("1/2")
+            .object(ScriptFilter::new("search", "gh", "github"))
+            .write_file(dir.join("info.plist"))
+            .unwrap();
+        let path = package(&dir).write_into(&out).unwrap();
+        assert_eq!(path, out.join("_.._evil-1_2.alfredworkflow"));
+        assert_eq!(path.parent(), Some(out.as_path()));
+
+        WorkflowDefinition::new("..", "..")
+            .object(ScriptFilter::new("search", "gh", "github"))
+            .write_file(dir.join("info.plist"))
+            .unwrap();
+        assert!(package(&dir).write_into(&out).is_err());
+    }
+
+    #[test]
+    fn test_write_into() {
+        let dir = workflow_dir("write");
+        let path = package(&dir).write_into(&dir).unwrap();
+        assert_eq!(path, dir.join("GitHub-1.2.0.alfredworkflow"));
+        assert!(path.is_file());
+
+        let zip = ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(zip.len(), 4);
+
+        // a failed check writes nothing
+        let bad = WorkflowPackage::new(dir.join("info.plist"));
+        assert!(bad.write_file(dir.join("bad.alfredworkflow")).is_err());
+        assert!(!dir.join("bad.alfredworkflow").exists());
+    }
+}
@@ -0,0 +1,285 @@
+use std::any::Any;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::icon::BuiltinIcon;
+use crate::workflow_error::Result;
+use crate::workflow_item::WorkflowItem;
+
+// how far the feedback json has been written
+// 0: nothing written, DONE: all written, PANICKED: the hook wrote the
+// recovery json and nothing may follow, n: inside `items` with n-1 items written
+const FEEDBACK_IDLE: usize = 0;
+const FEEDBACK_DONE: usize = usize::MAX;
+const FEEDBACK_PANICKED: usize = usize::MAX - 1;
+// the streamed items are written by batches of about this size
+const BATCH_SIZE: usize = 8 * 1024;
+
+static STDOUT: FeedbackOutput<Stdout> = FeedbackOutput::new(Stdout);
+
+// the feedback output the workflow sends to
+pub(crate) fn stdout() -> &'static FeedbackOutput<Stdout> {
+    &STDOUT
+}
+
+// where the feedback json goes, stdout or a buffer in tests
+pub(crate) trait Sink {
+    fn write_with<R>(&self, f: impl FnOnce(&mut dyn Write) -> R) -> R;
+}
+
+pub(crate) struct Stdout;
+
+impl Sink for Stdout {
+    fn write_with<R>(&self, f: impl FnOnce(&mut dyn Write) -> R) -> R {
+        f(&mut std::io::stdout().lock())
+    }
+}
+
+// the parts of the feedback json, every part is written as a whole
+pub(crate) trait FeedbackOut {
+    // `{"items":[`
+    fn begin(&mut self) -> Result<()>;
+    // one item, with the leading comma
+    fn item(&mut self, json: &[u8]) -> Result<()>;
+    // `]`, the other fields and `}`
+    fn end(&mut self, tail: &[u8]) -> Result<()>;
+}
+
+impl<W: Write> FeedbackOut for W {
+    fn begin(&mut self) -> Result<()> {
+        Ok(self.write_all(b"{\"items\":[")?)
+    }
+
+    fn item(&mut self, json: &[u8]) -> Result<()> {
+        Ok(self.write_all(json)?)
+    }
+
+    fn end(&mut self, tail: &[u8]) -> Result<()> {
+        self.write_all(tail)?;
+        Ok(self.flush()?)
+    }
+}
+
+// the feedback output shared by all threads and the panic hook
+// without the hook it is written as is, with the hook every write and the
+// progress change are made under one lock, so the hook either completes
+// what is written or writes nothing, never in between
+pub(crate) struct FeedbackOutput<S> {
+    hooked: AtomicBool,
+    progress: Mutex<Progress>,
+    sink: S,
+}
+
+struct Progress {
+    state: usize,
+    // streamed but not written yet, the hook writes it before its own json
+    pending: Vec<u8>,
+}
+
+impl<S: Sink> FeedbackOutput<S> {
+    pub(crate) const fn new(sink: S) -> FeedbackOutput<S> {
+        FeedbackOutput {
+            hooked: AtomicBool::new(false),
+            progress: Mutex::new(Progress {
+                state: FEEDBACK_IDLE,
+                pending: Vec::new(),
+            }),
+            sink,
+        }
+    }
+
+    pub(crate) fn is_hooked(&self) -> bool {
+        self.hooked.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn hook(&self) {
+        self.hooked.store(true, Ordering::SeqCst);
+    }
+
+    // nothing panics while it is held, a poisoned lock is still usable
+    fn lock(&self) -> MutexGuard<'_, Progress> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // the sink as is, for the output without the hook
+    pub(crate) fn write_with<R>(&self, f: impl FnOnce(&mut dyn Write) -> R) -> R {
+        self.sink.write_with(f)
+    }
+
+    // write the whole json at once, dropped after a reported panic
+    pub(crate) fn send(&self, write: impl FnOnce(&mut dyn Write) -> Result<()>) -> Result<()> {
+        if !self.is_hooked() {
+            return self.sink.write_with(write);
+        }
+        let mut progress = self.lock();
+        if progress.state == FEEDBACK_PANICKED {
+            log::warn!("the panic is already reported, the feedback is dropped");
+            return Ok(());
+        }
+        self.sink.write_with(write)?;
+        progress.state = FEEDBACK_DONE;
+        Ok(())
+    }
+
+    // the json part by part, for the output with the hook
+    pub(crate) fn stream(&self) -> Stream<'_, S> {
+        Stream { output: self }
+    }
+
+    // the hook: complete the json written so far with the panic item
+    pub(crate) fn report_panic(&self, message: &str, location: &str) {
+        let mut progress = self.lock();
+        if let Some(json) = recovery_feedback(progress.state, message, location) {
+            progress.pending.extend_from_slice(json.as_bytes());
+            if self.write_pending(&mut progress).is_ok() {
+                progress.state = FEEDBACK_PANICKED;
+            }
+        }
+    }
+
+    // add a part unless the panic is already reported, `last` writes
+    // everything out, otherwise only a full batch is written
+    fn write_part(&self, part: &[u8], next: impl FnOnce(usize) -> usize, last: bool) -> Result<()> {
+        let mut progress = self.lock();
+        if progress.state == FEEDBACK_PANICKED {
+            return Ok(());
+        }
+        progress.pending.extend_from_slice(part);
+        progress.state = next(progress.state);
+        if last || progress.pending.len() >= BATCH_SIZE {
+            self.write_pending(&mut progress)?;
+        }
+        Ok(())
+    }
+
+    fn write_pending(&self, progress: &mut Progress) -> std::io::Result<()> {
+        self.sink.write_with(|w| {
+            w.write_all(&progress.pending)?;
+            w.flush()
+        })?;
+        progress.pending.clear();
+        Ok(())
+    }
+}
+
+pub(crate) struct Stream<'a, S> {
+    output: &'a FeedbackOutput<S>,
+}
+
+impl<S: Sink> FeedbackOut for Stream<'_, S> {
+    fn begin(&mut self) -> Result<()> {
+        self.output.write_part(b"{\"items\":[", |_| 1, false)
+    }
+
+    fn item(&mut self, json: &[u8]) -> Result<()> {
+        self.output.write_part(json, |n| n + 1, false)
+    }
+
+    fn end(&mut self, tail: &[u8]) -> Result<()> {
+        self.output.write_part(tail, |_| FEEDBACK_DONE, true)
+    }
+}
+
+// log the panic and show it as an error item in alfred
+// the feedback already(or partly) written is completed to a valid json
+pub fn install_panic_hook() {
+    STDOUT.hook();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let message = panic_message(info.payload());
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        log::error!("workflow panicked at {}: {}", location, message);
+        STDOUT.report_panic(message.as_str(), location.as_str());
+        default_hook(info);
+    }));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+// the json to print for the panic, depends on what is already written
+fn recovery_feedback(state: usize, message: &str, location: &str) -> Option<String> {
+    let item = WorkflowItem::new("Workflow panicked")
+        .subtitle(format!("{} ({})", message, location).as_str())
+        .icon(BuiltinIcon::ERROR.get_icon())
+        .valid(false);
+    let item_json = serde_json::to_string(&item).ok()?;
+
+    match state {
+        FEEDBACK_IDLE => Some(format!("{{\"items\":[{}]}}", item_json)),
+        // the json is complete, or the panic is already reported
+        FEEDBACK_DONE | FEEDBACK_PANICKED => None,
+        1 => Some(format!("{}]}}", item_json)),
+        _ => Some(format!(",{}]}}", item_json)),
+    }
+}
+
+#[cfg(test)]
+mod workflow_panic_test {
+    use crate::workflow_panic::{
+        panic_message, recovery_feedback, FEEDBACK_DONE, FEEDBACK_IDLE, FEEDBACK_PANICKED,
+    };
+
+    fn parse(json: &str) -> serde_json::Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_recovery_before_feedback() {
+        let json = recovery_feedback(FEEDBACK_IDLE, "boom", "src/main.rs:1").unwrap();
+        let v = parse(json.as_str());
+        assert_eq!(v["items"][0]["title"], "Workflow panicked");
+        assert_eq!(v["items"][0]["subtitle"], "boom (src/main.rs:1)");
+        assert_eq!(v["items"][0]["valid"], false);
+        assert!(v["items"][0]["icon"]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("AlertStopIcon.icns"));
+    }
+
+    #[test]
+    fn test_recovery_while_streaming() {
+        let no_item = format!(
+            "{{\"items\":[{}",
+            recovery_feedback(1, "boom", "a.rs:2").unwrap()
+        );
+        assert_eq!(
+            parse(no_item.as_str())["items"].as_array().unwrap().len(),
+            1
+        );
+
+        let two_items = format!(
+            "{{\"items\":[{{\"title\":\"a\"}},{{\"title\":\"b\"}}{}",
+            recovery_feedback(3, "boom", "a.rs:2").unwrap()
+        );
+        let v = parse(two_items.as_str());
+        assert_eq!(v["items"].as_array().unwrap().len(), 3);
+        assert_eq!(v["items"][2]["title"], "Workflow panicked");
+    }
+
+    #[test]
+    fn test_no_recovery_after_feedback() {
+        assert!(recovery_feedback(FEEDBACK_DONE, "boom", "a.rs:2").is_none());
+        assert!(recovery_feedback(FEEDBACK_PANICKED, "boom", "a.rs:2").is_none());
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload = std::panic::catch_unwind(|| panic!("static msg")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "static msg");
+
+        let payload = std::panic::catch_unwind(|| panic!("formatted {}", 1)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "formatted 1");
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::workflow_item::WorkflowItem;
+
+// the embedded dictionary, see the header of the file for the format
+const PINYIN_DICT: &str = include_str!("pinyin.dict");
+
+// polyphonic chars make the variants grow exponentially, keep the first ones
+const MAX_VARIANTS: usize = 8;
+
+static DICT: OnceLock<HashMap<char, Vec<&'static str>>> = OnceLock::new();
+
+fn dict() -> &'static HashMap<char, Vec<&'static str>> {
+    DICT.get_or_init(|| {
+        let mut map: HashMap<char, Vec<&'static str>> = HashMap::new();
+        for line in PINYIN_DICT.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pinyin, chars) = match line.split_once(' ') {
+                Some(parts) => parts,
+                None => continue,
+            };
+            for c in chars.trim().chars() {
+                let readings = map.entry(c).or_default();
+                if !readings.contains(&pinyin) {
+                    readings.push(pinyin);
+                }
+            }
+        }
+        map
+    })
+}
+
+// all readings of the han char, None if the char is not in dictionary
+pub fn readings(c: char) -> Option<&'static [&'static str]> {
+    dict().get(&c).map(|v| v.as_slice())
+}
+
+// whether the text has any char known by the dictionary
+pub fn has_han(text: &str) -> bool {
+    text.chars().any(|c| readings(c).is_some())
+}
+
+// one spelling of the text, split into words
+// han chars are a word each, other chars are kept as they are
+#[derive(Debug, PartialEq)]
+pub struct PinyinVariant {
+    words: Vec<String>,
+}
+
+impl PinyinVariant {
+    // full pinyin joined, eg: 微信 => weixin
+    pub fn full(&self) -> String {
+        self.words.concat()
+    }
+
+    // full pinyin split by space, eg: 微信 => wei xin
+    pub fn spaced(&self) -> String {
+        self.words.join(" ")
+    }
+
+    // the first letter of every word, eg: 微信 => wx
+    pub fn initials(&self) -> String {
+        self.words.iter().filter_map(|w| w.chars().next()).collect()
+    }
+}
+
+// expand the text into pinyin spellings, polyphonic chars produce more than one
+// empty if the text has no han char
+pub fn variants(text: &str) -> Vec<PinyinVariant> {
+    if !has_han(text) {
+        return Vec::new();
+    }
+
+    let mut results: Vec<Vec<String>> = vec![Vec::new()];
+    let mut plain = String::new();
+    for c in text.chars() {
+        let readings = match readings(c) {
+            Some(r) => r,
+            None => {
+                if c.is_whitespace() {
+                    push_plain(&mut results, &mut plain);
+                } else {
+                    plain.extend(c.to_lowercase());
+                }
+                continue;
+            }
+        };
+        push_plain(&mut results, &mut plain);
+
+        let mut next: Vec<Vec<String>> = Vec::new();
+        for words in results.iter() {
+            for reading in readings {
+                if next.len() >= MAX_VARIANTS {
+                    break;
+                }
+                let mut w = words.clone();
+                w.push(reading.to_string());
+                next.push(w);
+            }
+        }
+        results = next;
+    }
+    push_plain(&mut results, &mut plain);
+
+    results
+        .into_iter()
+        .map(|words| PinyinVariant { words })
+        .collect()
+}
+
+fn push_plain(results: &mut [Vec<String>], plain: &mut String) {
+    if plain.is_empty() {
+        return;
+    }
+    for words in results.iter_mut() {
+        words.push(plain.clone());
+    }
+    plain.clear();
+}
+
+// the texts a query could be matched with: full, spaced and initials of every variant
+pub fn match_texts(text: &str) -> Vec<String> {
+    let mut texts: Vec<String> = Vec::new();
+    for v in variants(text) {
+        for t in [v.full(), v.spaced(), v.initials()] {
+            if !texts.contains(&t) {
+                texts.push(t);
+            }
+        }
+    }
+    texts
+}
+
+impl WorkflowItem {
+    // let alfred match the han title by pinyin, eg: `wx` or `weixin` => 微信
+    // the pinyin and initials are appended to the `match` field(or title)
+    pub fn pinyin_matches(self) -> WorkflowItem {
+        let text = self.match_text().to_string();
+        let mut words: Vec<String> = vec![text.clone()];
+        for v in variants(text.as_str()) {
+            for t in [v.full(), v.initials()] {
+                if !words.contains(&t) {
+                    words.push(t);
+                }
+            }
+        }
+        if words.len() == 1 {
+            return self;
+        }
+        self.matches(words.join(" ").as_str())
+    }
+}
+
+#[cfg(test)]
+mod workflow_pinyin_test {
+    use crate::workflow_filter::ItemFilter;
+    use crate::workflow_item::WorkflowItem;
+    use crate::workflow_pinyin::{dict, has_han, match_texts, readings, variants};
+
+    #[test]
+    fn test_dictionary_loaded() {
+        assert_eq!(readings('微'), Some(&["wei"][..]));
+        assert_eq!(readings('行'), Some(&["hang", "xing"][..]));
+        assert_eq!(readings('a'), None);
+        assert!(has_han("打开微信"));
+        assert!(!has_han("WeChat"));
+    }
+
+    #[test]
+    fn test_dictionary_covers_gb2312() {
+        assert!(dict().len() >= 6763);
+        for c in "支付宝淘搜索设置钉邮箱钥匙串访问".chars() {
+            assert!(readings(c).is_some(), "{}", c);
+        }
+        assert_eq!(readings('绿'), Some(&["lu", "lv"][..]));
+    }
+
+    #[test]
+    fn test_readings_of_common_polyphones() {
+        let cases = [
+            ('率', "shuai"),
+            ('率', "lv"),
+            ('什', "shi"),
+            ('没', "mo"),
+            ('数', "shuo"),
+            ('盛', "cheng"),
+            ('解', "xie"),
+            ('仇', "qiu"),
+        ];
+        for (c, reading) in cases {
+            assert!(readings(c).unwrap().contains(&reading), "{} {}", c, reading);
+        }
+    }
+
+    #[test]
+    fn test_variants_of_han_text() {
+        let v = variants("微信");
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].full(), "weixin");
+        assert_eq!(v[0].spaced(), "wei xin");
+        assert_eq!(v[0].initials(), "wx");
+    }
+
+    #[test]
+    fn test_variants_of_mixed_text() {
+        let v = variants("QQ音乐 Mac");
+        let spaced: Vec<String> = v.iter().map(|it| it.spaced()).collect();
+        assert_eq!(spaced, vec!["qq yin le mac", "qq yin yue mac"]);
+        assert_eq!(v[1].initials(), "qyym");
+    }
+
+    #[test]
+    fn test_variants_of_polyphonic_chars() {
+        let full: Vec<String> = variants("重庆银行").iter().map(|it| it.full()).collect();
+        assert_eq!(
+            full,
+            vec![
+                "chongqingyinhang",
+                "chongqingyinxing",
+                "zhongqingyinhang",
+                "zhongqingyinxing"
+            ]
+        );
+        assert!(variants("WeChat").is_empty());
+    }
+
+    #[test]
+    fn test_variants_limited() {
+        // every char has more than one reading
+        assert_eq!(variants("重行长乐和").len(), 8);
+    }
+
+    #[test]
+    fn test_match_texts() {
+        assert_eq!(match_texts("微信"), vec!["weixin", "wei xin", "wx"]);
+    }
+
+    #[test]
+    fn test_pinyin_matches_filled() {
+        let item = WorkflowItem::new("微信").pinyin_matches();
+        assert_eq!(item.match_text(), "微信 weixin wx");
+
+        let item = WorkflowItem::new("Safari").pinyin_matches();
+        assert_eq!(item.match_text(), "Safari");
+    }
+
+    #[test]
+    fn test_filter_with_pinyin() {
+        let items = vec![
+            WorkflowItem::new("微信"),
+            WorkflowItem::new("网易云音乐"),
+            WorkflowItem::new("WeChat Work"),
+        ];
+        let filter = ItemFilter::new().pinyin(true);
+        let titles = |items: Vec<WorkflowItem>| -> Vec<String> {
+            items.iter().map(|it| it.match_text().to_string()).collect()
+        };
+
+        let ranked = filter.filter("wx", items);
+        assert_eq!(titles(ranked), vec!["微信"]);
+
+        let items = vec![WorkflowItem::new("微信"), WorkflowItem::new("网易云音乐")];
+        assert_eq!(titles(filter.filter("weixin", items)), vec!["微信"]);
+
+        let items = vec![WorkflowItem::new("微信"), WorkflowItem::new("网易云音乐")];
+        assert_eq!(titles(filter.filter("wyyyy", items)), vec!["网易云音乐"]);
+
+        let items = vec![WorkflowItem::new("淘宝"), WorkflowItem::new("支付宝")];
+        assert_eq!(titles(filter.filter("zfb", items)), vec!["支付宝"]);
+
+        let items = vec![WorkflowItem::new("钉钉"), WorkflowItem::new("系统设置")];
+        assert_eq!(titles(filter.filter("xtsz", items)), vec!["系统设置"]);
+
+        let items = vec![WorkflowItem::new("效率"), WorkflowItem::new("什么值得买")];
+        assert_eq!(titles(filter.filter("xiaolv", items)), vec!["效率"]);
+
+        let items = vec![WorkflowItem::new("元帅"), WorkflowItem::new("率先")];
+        assert_eq!(titles(filter.filter("shuaixian", items)), vec!["率先"]);
+
+        let items = vec![WorkflowItem::new("微信")];
+        assert!(ItemFilter::new().filter("wx", items).is_empty());
+    }
+}
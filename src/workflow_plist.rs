@@ -0,0 +1,405 @@
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use plist::{Dictionary, Value};
+
+use crate::workflow::AlfredWorkflow;
+use crate::workflow_error::{Result, WorkflowError};
+
+pub const INFO_PLIST: &str = "info.plist";
+pub const SCRIPT_FILTER: &str = "alfred.workflow.input.scriptfilter";
+
+// the workflow metadata from `info.plist`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorkflowInfo {
+    pub bundle_id: Option<String>,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub web_address: Option<String>,
+    // in the order of the plist
+    pub variables: Vec<(String, String)>,
+    pub variables_dont_export: Vec<String>,
+    pub user_configuration: Vec<UserConfigField>,
+    pub objects: Vec<WorkflowObject>,
+    pub connections: Vec<Connection>,
+}
+
+// a field of the "User Configuration" sheet
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserConfigField {
+    pub variable: String,
+    // textfield, textarea, checkbox, popupbutton, filepicker, slider ...
+    pub kind: String,
+    pub label: Option<String>,
+    pub description: Option<String>,
+    // as the variable would be set, checkbox is `1` or `0`
+    pub default: Option<String>,
+    pub placeholder: Option<String>,
+    pub required: bool,
+}
+
+// an input, action, output etc. on the workflow canvas
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorkflowObject {
+    pub uid: String,
+    // e.g. `alfred.workflow.input.scriptfilter`
+    pub kind: String,
+    pub version: Option<i64>,
+    // the object specific settings as they are
+    pub config: Dictionary,
+}
+
+// a wire between two objects
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Connection {
+    pub from: String,
+    pub to: String,
+    // the modifier key mask, 0 for plain enter
+    pub modifiers: u64,
+    pub modifier_subtext: Option<String>,
+    pub vito_close: bool,
+}
+
+impl WorkflowObject {
+    pub fn is_script_filter(&self) -> bool {
+        self.kind == SCRIPT_FILTER
+    }
+
+    pub fn keyword(&self) -> Option<&str> {
+        text(&self.config, "keyword")
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        text(&self.config, "title")
+    }
+
+    pub fn script(&self) -> Option<&str> {
+        text(&self.config, "script")
+    }
+
+    // the script file in the workflow folder, for external scripts
+    pub fn script_file(&self) -> Option<&str> {
+        text(&self.config, "scriptfile")
+    }
+}
+
+impl WorkflowInfo {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<WorkflowInfo> {
+        WorkflowInfo::from_value(Value::from_file(path)?)
+    }
+
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<WorkflowInfo> {
+        WorkflowInfo::from_value(Value::from_reader(reader)?)
+    }
+
+    pub fn from_value(value: Value) -> Result<WorkflowInfo> {
+        let root = value
+            .into_dictionary()
+            .ok_or_else(|| WorkflowError::Invalid("info.plist is not a dictionary".to_string()))?;
+
+        let variables = dictionary(&root, "variables")
+            .map(|vars| {
+                vars.iter()
+                    .map(|(k, v)| (k.clone(), scalar(v).unwrap_or_default()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let variables_dont_export = array(&root, "variablesdontexport")
+            .filter_map(|v| v.as_string().map(String::from))
+            .collect();
+        let user_configuration = array(&root, "userconfigurationconfig")
+            .filter_map(Value::as_dictionary)
+            .map(user_config_field)
+            .collect();
+        let objects = array(&root, "objects")
+            .filter_map(Value::as_dictionary)
+            .map(workflow_object)
+            .collect();
+        let connections = dictionary(&root, "connections")
+            .map(|wires| {
+                wires
+                    .iter()
+                    .flat_map(|(from, to)| {
+                        to.as_array()
+                            .into_iter()
+                            .flatten()
+                            .filter_map(Value::as_dictionary)
+                            .map(move |c| connection(from, c))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(WorkflowInfo {
+            bundle_id: owned(&root, "bundleid"),
+            name: owned(&root, "name"),
+            version: owned(&root, "version"),
+            description: owned(&root, "description"),
+            created_by: owned(&root, "createdby"),
+            web_address: owned(&root, "webaddress"),
+            variables,
+            variables_dont_export,
+            user_configuration,
+            objects,
+            connections,
+        })
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.variables
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn object(&self, uid: &str) -> Option<&WorkflowObject> {
+        self.objects.iter().find(|o| o.uid == uid)
+    }
+
+    pub fn script_filters(&self) -> impl Iterator<Item = &WorkflowObject> {
+        self.objects.iter().filter(|o| o.is_script_filter())
+    }
+
+    // keywords of all the script filters
+    pub fn keywords(&self) -> Vec<&str> {
+        self.script_filters().filter_map(|o| o.keyword()).collect()
+    }
+
+    pub fn connections_from<'a>(&'a self, uid: &'a str) -> impl Iterator<Item = &'a Connection> {
+        self.connections.iter().filter(move |c| c.from == uid)
+    }
+}
+
+// `info.plist` in the folder, alfred runs the scripts in the workflow folder
+pub fn locate<P: AsRef<Path>>(dir: P) -> Result<PathBuf> {
+    let path = dir.as_ref().join(INFO_PLIST);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} not found", path.display()),
+        )
+        .into())
+    }
+}
+
+impl AlfredWorkflow {
+    // the current folder, or the workflow folder in alfred preferences
+    pub fn info_path(&self) -> Result<PathBuf> {
+        let ctx = self.context();
+        locate(std::env::current_dir()?).or_else(|e| {
+            match (ctx.preferences(), ctx.workflow_uid()) {
+                (Some(prefs), Some(uid)) => locate(prefs.join("workflows").join(uid)),
+                _ => Err(e),
+            }
+        })
+    }
+
+    pub fn info(&self) -> Result<WorkflowInfo> {
+        WorkflowInfo::from_file(self.info_path()?)
+    }
+}
+
+fn text<'a>(dict: &'a Dictionary, key: &str) -> Option<&'a str> {
+    dict.get(key)
+        .and_then(Value::as_string)
+        .filter(|v| !v.is_empty())
+}
+
+fn owned(dict: &Dictionary, key: &str) -> Option<String> {
+    text(dict, key).map(String::from)
+}
+
+fn dictionary<'a>(dict: &'a Dictionary, key: &str) -> Option<&'a Dictionary> {
+    dict.get(key).and_then(Value::as_dictionary)
+}
+
+fn array<'a>(dict: &'a Dictionary, key: &str) -> impl Iterator<Item = &'a Value> {
+    dict.get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+// a value as the environment variable would be set
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Boolean(b) => Some(if *b { "1" } else { "0" }.to_string()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Real(r) => Some(r.to_string()),
+        _ => None,
+    }
+}
+
+fn user_config_field(dict: &Dictionary) -> UserConfigField {
+    let empty = Dictionary::new();
+    let config = dictionary(dict, "config").unwrap_or(&empty);
+    UserConfigField {
+        variable: owned(dict, "variable").unwrap_or_default(),
+        kind: owned(dict, "type").unwrap_or_default(),
+        label: owned(dict, "label"),
+        description: owned(dict, "description"),
+        default: config
+            .get("default")
+            .and_then(scalar)
+            .filter(|v| !v.is_empty()),
+        placeholder: owned(config, "placeholder"),
+        required: config
+            .get("required")
+            .and_then(Value::as_boolean)
+            .unwrap_or(false),
+    }
+}
+
+fn workflow_object(dict: &Dictionary) -> WorkflowObject {
+    WorkflowObject {
+        uid: owned(dict, "uid").unwrap_or_default(),
+        kind: owned(dict, "type").unwrap_or_default(),
+        version: dict.get("version").and_then(Value::as_signed_integer),
+        config: dictionary(dict, "config").cloned().unwrap_or_default(),
+    }
+}
+
+fn connection(from: &str, dict: &Dictionary) -> Connection {
+    Connection {
+        from: from.to_string(),
+        to: owned(dict, "destinationuid").unwrap_or_default(),
+        modifiers: dict
+            .get("modifiers")
+            .and_then(Value::as_unsigned_integer)
+            .unwrap_or(0),
+        modifier_subtext: owned(dict, "modifiersubtext"),
+        vito_close: dict
+            .get("vitoclose")
+            .and_then(Value::as_boolean)
+            .unwrap_or(false),
+    }
+}
+
+#[cfg(test)]
+mod workflow_plist_test {
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    use crate::alfred_context::AlfredContext;
+    use crate::test_util::TempDir;
+    use crate::workflow::AlfredWorkflow;
+    use crate::workflow_plist::{locate, WorkflowInfo};
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let info = WorkflowInfo::from_file(fixture("info.plist")).unwrap();
+        assert_eq!(info.bundle_id.as_deref(), Some("com.demo.github"));
+        assert_eq!(info.name.as_deref(), Some("GitHub"));
+        assert_eq!(info.version.as_deref(), Some("1.2.0"));
+        assert_eq!(info.created_by.as_deref(), Some("christmic"));
+        assert_eq!(
+            info.description.as_deref(),
+            Some("Search github repositories")
+        );
+    }
+
+    #[test]
+    fn test_parse_variables() {
+        let info = WorkflowInfo::from_file(fixture("info.plist")).unwrap();
+        let names: Vec<&str> = info.variables.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(names, vec!["page_size", "api_host", "secret"]);
+        assert_eq!(info.variable("page_size"), Some("20"));
+        assert_eq!(info.variables_dont_export, vec!["secret"]);
+    }
+
+    #[test]
+    fn test_parse_user_configuration() {
+        let info = WorkflowInfo::from_file(fixture("info.plist")).unwrap();
+        let fields = &info.user_configuration;
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].variable, "api_token");
+        assert_eq!(fields[0].kind, "textfield");
+        assert_eq!(fields[0].label.as_deref(), Some("API Token"));
+        assert_eq!(fields[0].placeholder.as_deref(), Some("ghp_xxx"));
+        assert_eq!(fields[0].default, None);
+        assert!(fields[0].required);
+        assert_eq!(fields[1].kind, "checkbox");
+        assert_eq!(fields[1].default.as_deref(), Some("1"));
+        assert_eq!(fields[1].description, None);
+    }
+
+    #[test]
+    fn test_parse_objects_and_connections() {
+        let info = WorkflowInfo::from_file(fixture("info.plist")).unwrap();
+        assert_eq!(info.objects.len(), 3);
+        assert_eq!(info.keywords(), vec!["gh"]);
+
+        let filter = info.script_filters().next().unwrap();
+        assert_eq!(filter.title(), Some("Search GitHub"));
+        assert_eq!(filter.script(), Some(r#"./github "$1""#));
+        assert_eq!(filter.version, Some(3));
+
+        let wires: Vec<_> = info.connections_from(&filter.uid).collect();
+        assert_eq!(wires.len(), 2);
+        assert_eq!(
+            info.object(&wires[0].to).unwrap().kind,
+            "alfred.workflow.action.openurl"
+        );
+        assert_eq!(wires[0].modifiers, 0);
+        assert_eq!(wires[0].modifier_subtext, None);
+        assert_eq!(wires[1].modifiers, 1048576);
+        assert_eq!(wires[1].modifier_subtext.as_deref(), Some("Copy the url"));
+        assert!(wires[1].vito_close);
+    }
+
+    #[test]
+    fn test_parse_minimal() {
+        let info = WorkflowInfo::from_file(fixture("minimal.plist")).unwrap();
+        assert_eq!(info.bundle_id.as_deref(), Some("com.demo.minimal"));
+        assert!(info.variables.is_empty());
+        assert!(info.objects.is_empty());
+        assert!(info.connections.is_empty());
+        assert!(info.keywords().is_empty());
+    }
+
+    #[test]
+    fn test_parse_not_a_plist() {
+        let reader = std::io::Cursor::new(b"not a plist".to_vec());
+        assert!(WorkflowInfo::from_reader(reader).is_err());
+    }
+
+    #[test]
+    fn test_locate() {
+        assert!(locate(fixture("")).is_ok());
+        assert!(locate("/not/exist").is_err());
+    }
+
+    #[test]
+    fn test_workflow_info_from_preferences() {
+        // the plist is looked up in `<preferences>/workflows/<uid>`
+        let prefs = TempDir::new("plist_preferences");
+        let dir = prefs.join("workflows").join("user.workflow.DEMO");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(fixture("minimal.plist"), dir.join("info.plist")).unwrap();
+
+        let ctx = AlfredContext::from_map(HashMap::from([
+            (
+                "alfred_preferences".to_string(),
+                prefs.to_str().unwrap().to_string(),
+            ),
+            (
+                "alfred_workflow_uid".to_string(),
+                "user.workflow.DEMO".to_string(),
+            ),
+        ]));
+        let wf = AlfredWorkflow::from_context(ctx);
+        assert_eq!(wf.info_path().unwrap(), dir.join("info.plist"));
+        assert_eq!(wf.info().unwrap().name.as_deref(), Some("Minimal"));
+    }
+}
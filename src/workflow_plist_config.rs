@@ -0,0 +1,275 @@
+use std::path::{Path, PathBuf};
+
+use plist::{Dictionary, Value};
+
+use crate::workflow::AlfredWorkflow;
+use crate::workflow_error::{Result, WorkflowError};
+use crate::workflow_plist::WorkflowInfo;
+
+const VARIABLES: &str = "variables";
+const DONT_EXPORT: &str = "variablesdontexport";
+
+// edit the workflow variables in `info.plist` directly, works without
+// a running alfred(scripts, tests, ci), unlike `Alfred::set_config`
+// the order of the keys and the keys not touched are kept as they are
+pub struct PlistConfig {
+    path: PathBuf,
+    root: Dictionary,
+}
+
+impl PlistConfig {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<PlistConfig> {
+        let path = path.as_ref().to_path_buf();
+        let root = Value::from_file(&path)?
+            .into_dictionary()
+            .ok_or_else(|| WorkflowError::Invalid("info.plist is not a dictionary".to_string()))?;
+        Ok(PlistConfig { path, root })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_config(&self, name: &str) -> Option<&str> {
+        self.root
+            .get(VARIABLES)
+            .and_then(Value::as_dictionary)
+            .and_then(|vars| vars.get(name))
+            .and_then(Value::as_string)
+    }
+
+    // a new variable is added after the existing ones, an existing one keeps its place
+    pub fn set_config(&mut self, name: &str, value: &str) -> Result<&mut PlistConfig> {
+        self.variables()?
+            .insert(name.to_string(), Value::from(value));
+        Ok(self)
+    }
+
+    pub fn remove_config(&mut self, name: &str) -> Result<&mut PlistConfig> {
+        match self.root.get_mut(VARIABLES) {
+            None => {}
+            Some(Value::Dictionary(vars)) => vars.retain(|k, _| k != name),
+            Some(_) => return Err(unexpected(&self.path, VARIABLES, "a dictionary")),
+        }
+        self.keep_exported(name)?;
+        Ok(self)
+    }
+
+    pub fn is_exportable(&self, name: &str) -> bool {
+        !self
+            .root
+            .get(DONT_EXPORT)
+            .and_then(Value::as_array)
+            .map(|names| names.iter().any(|v| v.as_string() == Some(name)))
+            .unwrap_or(false)
+    }
+
+    // whether the value is kept when the workflow is exported(shared)
+    pub fn exportable(&mut self, name: &str, exportable: bool) -> Result<&mut PlistConfig> {
+        if exportable {
+            self.keep_exported(name)?;
+        } else if self.is_exportable(name) {
+            self.dont_export()?.push(Value::from(name));
+        }
+        Ok(self)
+    }
+
+    pub fn info(&self) -> Result<WorkflowInfo> {
+        WorkflowInfo::from_value(Value::Dictionary(self.root.clone()))
+    }
+
+    // written to a temp file first, so alfred never sees a half written plist
+    pub fn save(&self) -> Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        Value::Dictionary(self.root.clone()).to_file_xml(&tmp)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn variables(&mut self) -> Result<&mut Dictionary> {
+        if !self.root.contains_key(VARIABLES) {
+            self.root
+                .insert(VARIABLES.to_string(), Value::Dictionary(Dictionary::new()));
+        }
+        match self.root.get_mut(VARIABLES) {
+            Some(Value::Dictionary(vars)) => Ok(vars),
+            _ => Err(unexpected(&self.path, VARIABLES, "a dictionary")),
+        }
+    }
+
+    fn dont_export(&mut self) -> Result<&mut Vec<Value>> {
+        if !self.root.contains_key(DONT_EXPORT) {
+            self.root
+                .insert(DONT_EXPORT.to_string(), Value::Array(Vec::new()));
+        }
+        match self.root.get_mut(DONT_EXPORT) {
+            Some(Value::Array(names)) => Ok(names),
+            _ => Err(unexpected(&self.path, DONT_EXPORT, "an array")),
+        }
+    }
+
+    fn keep_exported(&mut self, name: &str) -> Result<()> {
+        match self.root.get_mut(DONT_EXPORT) {
+            None => Ok(()),
+            Some(Value::Array(names)) => {
+                names.retain(|v| v.as_string() != Some(name));
+                Ok(())
+            }
+            Some(_) => Err(unexpected(&self.path, DONT_EXPORT, "an array")),
+        }
+    }
+}
+
+// a key of another type is user data, it is reported and never overwritten
+fn unexpected(path: &Path, key: &str, expected: &str) -> WorkflowError {
+    WorkflowError::Invalid(format!(
+        "`{}` in {} is not {}",
+        key,
+        path.display(),
+        expected
+    ))
+}
+
+impl AlfredWorkflow {
+    // the variables of this workflow's `info.plist`, see `info_path`
+    pub fn plist_config(&self) -> Result<PlistConfig> {
+        PlistConfig::open(self.info_path()?)
+    }
+}
+
+#[cfg(test)]
+mod workflow_plist_config_test {
+    use std::path::{Path, PathBuf};
+
+    use plist::Value;
+
+    use crate::test_util::TempDir;
+    use crate::workflow_plist_config::PlistConfig;
+
+    // the copied fixture is removed with the folder
+    fn copy_fixture(name: &str, test: &str) -> (TempDir, PathBuf) {
+        let dir = TempDir::new(&format!("plist_config_{}", test));
+        let path = dir.join("info.plist");
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join(name);
+        std::fs::copy(fixture, &path).unwrap();
+        (dir, path)
+    }
+
+    fn keys(value: &Value) -> Vec<String> {
+        value.as_dictionary().unwrap().keys().cloned().collect()
+    }
+
+    #[test]
+    fn test_set_and_remove_keep_order() {
+        let (_dir, path) = copy_fixture("info.plist", "order");
+        let before = Value::from_file(&path).unwrap();
+
+        let mut config = PlistConfig::open(&path).unwrap();
+        config
+            .set_config("api_host", "ghe.local")
+            .unwrap()
+            .set_config("timeout", "30")
+            .unwrap()
+            .remove_config("page_size")
+            .unwrap();
+        config.save().unwrap();
+
+        let after = Value::from_file(&path).unwrap();
+        // unknown keys like `uidata` and `category` are kept in place
+        assert_eq!(keys(&before), keys(&after));
+        let info = PlistConfig::open(&path).unwrap().info().unwrap();
+        assert_eq!(
+            info.variables,
+            vec![
+                ("api_host".to_string(), "ghe.local".to_string()),
+                ("secret".to_string(), "hidden".to_string()),
+                ("timeout".to_string(), "30".to_string()),
+            ]
+        );
+        assert_eq!(
+            before.as_dictionary().unwrap().get("uidata"),
+            after.as_dictionary().unwrap().get("uidata")
+        );
+    }
+
+    #[test]
+    fn test_exportable() {
+        let (_dir, path) = copy_fixture("info.plist", "export");
+        let mut config = PlistConfig::open(&path).unwrap();
+        assert!(!config.is_exportable("secret"));
+        assert!(config.is_exportable("page_size"));
+
+        config
+            .exportable("page_size", false)
+            .unwrap()
+            .exportable("page_size", false)
+            .unwrap()
+            .exportable("secret", true)
+            .unwrap();
+        config.save().unwrap();
+
+        let info = PlistConfig::open(&path).unwrap().info().unwrap();
+        assert_eq!(info.variables_dont_export, vec!["page_size"]);
+
+        // removing a variable also removes it from the dont export list
+        let mut config = PlistConfig::open(&path).unwrap();
+        config.remove_config("page_size").unwrap();
+        assert!(config.is_exportable("page_size"));
+    }
+
+    #[test]
+    fn test_add_variables_to_minimal() {
+        let (_dir, path) = copy_fixture("minimal.plist", "minimal");
+        let mut config = PlistConfig::open(&path).unwrap();
+        assert_eq!(config.get_config("token"), None);
+        config
+            .set_config("token", "abc")
+            .unwrap()
+            .exportable("token", false)
+            .unwrap();
+        config.save().unwrap();
+
+        let config = PlistConfig::open(&path).unwrap();
+        assert_eq!(config.get_config("token"), Some("abc"));
+        assert!(!config.is_exportable("token"));
+        assert_eq!(
+            config.info().unwrap().bundle_id.as_deref(),
+            Some("com.demo.minimal")
+        );
+        assert!(!path.with_extension("plist.tmp").exists());
+    }
+
+    #[test]
+    fn test_unexpected_types_kept() {
+        let (_dir, path) = copy_fixture("minimal.plist", "types");
+        let mut root = Value::from_file(&path).unwrap().into_dictionary().unwrap();
+        root.insert("variables".to_string(), Value::from("not a dict"));
+        root.insert("variablesdontexport".to_string(), Value::from(1));
+        Value::Dictionary(root).to_file_xml(&path).unwrap();
+
+        let mut config = PlistConfig::open(&path).unwrap();
+        let err = config.set_config("token", "abc").err().unwrap();
+        assert!(err.to_string().contains("`variables`"));
+        assert!(config.remove_config("token").is_err());
+        assert!(config.exportable("token", false).is_err());
+        assert!(config.exportable("token", true).is_err());
+        config.save().unwrap();
+
+        // the user data is not overwritten
+        let after = Value::from_file(&path).unwrap();
+        let after = after.as_dictionary().unwrap();
+        assert_eq!(
+            after.get("variables").and_then(Value::as_string),
+            Some("not a dict")
+        );
+        assert!(after.get("variablesdontexport").unwrap().as_array().is_none());
+    }
+
+    #[test]
+    fn test_open_missing() {
+        assert!(PlistConfig::open("/not/exist/info.plist").is_err());
+    }
+}
@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::workflow_error::{Result, WorkflowError};
+use crate::workflow_item::WorkflowItem;
+
+// the `rerun` range alfred accepts, in seconds
+const RERUN_MIN: f32 = 0.1;
+const RERUN_MAX: f32 = 5.0;
+
+// the script filter output as alfred reads it
+#[derive(Deserialize)]
+pub struct Feedback {
+    pub items: Vec<WorkflowItem>,
+    #[serde(default)]
+    pub rerun: Option<f32>,
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default, rename = "skipknowledge")]
+    pub skip_knowledge: bool,
+}
+
+impl Feedback {
+    pub fn parse(json: &str) -> Result<Feedback> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    // the delay before alfred runs the script again with the same query,
+    // a value alfred would not accept is an error of the script filter
+    pub fn rerun_delay(&self) -> Result<Option<Duration>> {
+        let rerun = match self.rerun {
+            Some(rerun) => rerun,
+            None => return Ok(None),
+        };
+        let invalid = || {
+            WorkflowError::Invalid(format!(
+                "rerun must be between {} and {} seconds, got {}",
+                RERUN_MIN, RERUN_MAX, rerun
+            ))
+        };
+        if !(RERUN_MIN..=RERUN_MAX).contains(&rerun) {
+            return Err(invalid());
+        }
+        Duration::try_from_secs_f32(rerun)
+            .map(Some)
+            .map_err(|_| invalid())
+    }
+
+    // the query after pressing tab on the item, 1 based like the rendering
+    pub fn auto_complete(&self, index: usize) -> Option<&str> {
+        let item = self.items.get(index.checked_sub(1)?)?;
+        Some(item.get_auto_complete().unwrap_or(item.get_title()))
+    }
+}
+
+// how the script filter is run, read from a json or toml(by the `.toml`
+// extension) file, every field is optional
+// {
+//   "executable": "target/debug/my-workflow",
+//   "args": [],
+//   "working_dir": ".",
+//   "alfred_version": "5.0.6",
+//   "bundle_id": "com.demo.workflow",
+//   "env": {"api_token": "xyz"},
+//   "max_reruns": 3
+// }
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SimulatorProfile {
+    pub executable: Option<PathBuf>,
+    // passed before the query
+    pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+    pub alfred_version: Option<String>,
+    pub bundle_id: Option<String>,
+    // the workflow variables, they win over the generated alfred ones
+    pub env: HashMap<String, String>,
+    // stop following `rerun` after so many runs
+    pub max_reruns: Option<u32>,
+}
+
+impl SimulatorProfile {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SimulatorProfile> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            return toml::from_str(&text).map_err(|e| {
+                WorkflowError::Invalid(format!("bad profile {}: {}", path.display(), e))
+            });
+        }
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn executable<P: AsRef<Path>>(mut self, path: P) -> SimulatorProfile {
+        self.executable = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    // the environment alfred would give the script, plus PATH and HOME
+    pub fn environment(&self) -> HashMap<String, String> {
+        let bundle_id = self
+            .bundle_id
+            .clone()
+            .unwrap_or_else(|| "com.alfred.simulator".to_string());
+        let version = self
+            .alfred_version
+            .clone()
+            .unwrap_or_else(|| "5.0".to_string());
+        let root = std::env::temp_dir().join("alfred-simulator");
+        let dir = |name: &str| root.join(name).join(&bundle_id).display().to_string();
+
+        let mut env: HashMap<String, String> = ["PATH", "HOME", "USER", "LANG"]
+            .iter()
+            .filter_map(|k| std::env::var(k).ok().map(|v| (k.to_string(), v)))
+            .collect();
+        let alfred = [
+            ("alfred_version", version),
+            ("alfred_version_build", "2000".to_string()),
+            ("alfred_preferences", dir("preferences")),
+            ("alfred_preferences_localhash", "simulator".to_string()),
+            ("alfred_theme", "theme.bundled.default".to_string()),
+            ("alfred_theme_subtext", "0".to_string()),
+            ("alfred_workflow_bundleid", bundle_id.clone()),
+            ("alfred_workflow_name", "Simulator".to_string()),
+            ("alfred_workflow_uid", "user.workflow.SIMULATOR".to_string()),
+            ("alfred_workflow_cache", dir("cache")),
+            ("alfred_workflow_data", dir("data")),
+            ("alfred_debug", "1".to_string()),
+        ];
+        env.extend(alfred.into_iter().map(|(k, v)| (k.to_string(), v)));
+        env.extend(self.env.clone());
+        env
+    }
+}
+
+// run a script filter like alfred does, outside alfred
+pub struct Simulator {
+    profile: SimulatorProfile,
+}
+
+impl Simulator {
+    pub fn new(profile: SimulatorProfile) -> Simulator {
+        Simulator { profile }
+    }
+
+    pub fn profile(&self) -> &SimulatorProfile {
+        &self.profile
+    }
+
+    pub fn max_reruns(&self) -> u32 {
+        self.profile.max_reruns.unwrap_or(3)
+    }
+
+    // run with the query as the last argument, `variables` of the last
+    // feedback are passed on like alfred does on rerun
+    pub fn run(&self, query: &str, variables: &HashMap<String, String>) -> Result<Feedback> {
+        let exec = self
+            .profile
+            .executable
+            .as_ref()
+            .ok_or_else(|| WorkflowError::Invalid("no executable to simulate".to_string()))?;
+        let mut command = Command::new(exec);
+        command
+            .args(&self.profile.args)
+            .arg(query)
+            .env_clear()
+            .envs(self.profile.environment())
+            .envs(variables)
+            .stdin(Stdio::null());
+        if let Some(dir) = &self.profile.working_dir {
+            command.current_dir(dir);
+        }
+
+        let output = command.output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(WorkflowError::Invalid(format!(
+                "script filter exited with {}: {}",
+                output.status,
+                stderr.trim()
+            )));
+        }
+        Feedback::parse(&stdout)
+    }
+}
+
+// the feedback as text, close to what alfred shows
+//  1. Title
+//     subtitle
+//     arg: a | autocomplete: b
+//     cmd: subtitle of the modifier
+pub fn render(feedback: &Feedback) -> String {
+    let mut out = String::new();
+    if feedback.items.is_empty() {
+        out.push_str("(no items, alfred shows the fallback searches)\n");
+    }
+    for (i, item) in feedback.items.iter().enumerate() {
+        let invalid = if item.is_valid() { "" } else { "  [invalid]" };
+        let _ = writeln!(out, "{:>2}. {}{}", i + 1, item.get_title(), invalid);
+        if let Some(subtitle) = item.get_subtitle().filter(|s| !s.is_empty()) {
+            let _ = writeln!(out, "    {}", subtitle);
+        }
+
+        let mut details = Vec::new();
+        if !item.get_args().is_empty() {
+            details.push(format!("arg: {}", item.get_args().join(", ")));
+        }
+        if let Some(auto) = item.get_auto_complete() {
+            details.push(format!("autocomplete: {}", auto));
+        }
+        if !details.is_empty() {
+            let _ = writeln!(out, "    {}", details.join(" | "));
+        }
+
+        // sorted, so the output is the same on every run
+        let mods: BTreeMap<_, _> = item.get_mods().into_iter().flatten().collect();
+        for (key, modifier) in mods {
+            let invalid = if modifier.is_valid() {
+                ""
+            } else {
+                "  [invalid]"
+            };
+            let _ = writeln!(out, "    {}: {}{}", key, modifier.get_subtitle(), invalid);
+        }
+    }
+    if !feedback.variables.is_empty() {
+        let vars: BTreeMap<_, _> = feedback.variables.iter().collect();
+        let vars: Vec<String> = vars.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let _ = writeln!(out, "variables: {}", vars.join(", "));
+    }
+    if let Some(rerun) = feedback.rerun {
+        let _ = writeln!(out, "rerun in {}s", rerun);
+    }
+    out
+}
+
+#[cfg(test)]
+mod workflow_simulator_test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::test_util::TempDir;
+    use crate::workflow_simulator::{render, Feedback, Simulator, SimulatorProfile};
+
+    #[test]
+    fn test_render_feedback() {
+        let feedback = Feedback::parse(
+            r#"{"items": [
+                {"title": "Open", "subtitle": "open it", "arg": "x", "autocomplete": "op",
+                 "mods": {"shift": {"subtitle": "s"}, "cmd": {"subtitle": "c", "valid": false}}},
+                {"title": "Nothing", "valid": false}
+            ], "rerun": 0.5, "variables": {"page": "2"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            render(&feedback),
+            " 1. Open\n    open it\n    arg: x | autocomplete: op\n    cmd: c  [invalid]\n    shift: s\n \
+             2. Nothing  [invalid]\nvariables: page=2\nrerun in 0.5s\n"
+        );
+        assert_eq!(feedback.auto_complete(1), Some("op"));
+        assert_eq!(feedback.auto_complete(2), Some("Nothing"));
+        assert_eq!(feedback.auto_complete(0), None);
+        assert_eq!(feedback.auto_complete(3), None);
+    }
+
+    #[test]
+    fn test_rerun_delay() {
+        let rerun = |json: &str| Feedback::parse(json).unwrap().rerun_delay();
+        assert_eq!(rerun(r#"{"items": []}"#).unwrap(), None);
+        assert_eq!(
+            rerun(r#"{"items": [], "rerun": 0.5}"#).unwrap(),
+            Some(Duration::from_millis(500))
+        );
+        assert!(rerun(r#"{"items": [], "rerun": 5}"#).unwrap().is_some());
+
+        // alfred only takes 0.1 to 5 seconds, so these never panic
+        for bad in ["-1", "0", "0.05", "5.5", "1e40"] {
+            let err = rerun(&format!(r#"{{"items": [], "rerun": {}}}"#, bad))
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("between 0.1 and 5"), "{}", bad);
+        }
+        let nan = Feedback {
+            items: Vec::new(),
+            rerun: Some(f32::NAN),
+            variables: HashMap::new(),
+            skip_knowledge: false,
+        };
+        assert!(nan.rerun_delay().is_err());
+    }
+
+    #[test]
+    fn test_profile_from_file() {
+        let dir = TempDir::new("simulator_profile");
+        let toml = dir.join("profile.toml");
+        std::fs::write(
+            &toml,
+            "executable = \"target/debug/wf\"\nmax_reruns = 1\n\n[env]\napi_token = \"xyz\"\n",
+        )
+        .unwrap();
+        let json = dir.join("profile.json");
+        std::fs::write(
+            &json,
+            r#"{"executable": "target/debug/wf", "max_reruns": 1}"#,
+        )
+        .unwrap();
+
+        let profile = SimulatorProfile::from_file(&toml).unwrap();
+        assert_eq!(profile.executable, Some(PathBuf::from("target/debug/wf")));
+        assert_eq!(profile.env["api_token"], "xyz");
+        assert_eq!(Simulator::new(profile).max_reruns(), 1);
+        assert_eq!(
+            SimulatorProfile::from_file(&json).unwrap().max_reruns,
+            Some(1)
+        );
+
+        std::fs::write(&toml, "max_reruns = \"many\"").unwrap();
+        assert!(SimulatorProfile::from_file(&toml).is_err());
+    }
+
+    #[test]
+    fn test_run_with_profile_env() {
+        // a script filter echoing the query and the environment
+        let dir = TempDir::new("simulator_env");
+        let exec = dir.script(
+            "env",
+            r#"printf '{"items":[{"title":"%s","subtitle":"%s %s %s"}]}' "$1" "$alfred_version" "$api_token" "$page""#,
+        );
+        let profile: SimulatorProfile =
+            serde_json::from_str(r#"{"alfred_version": "4.6", "env": {"api_token": "xyz"}}"#)
+                .unwrap();
+        let simulator = Simulator::new(profile.executable(&exec));
+
+        let vars = HashMap::from([("page".to_string(), "2".to_string())]);
+        let feedback = simulator.run("hello world", &vars).unwrap();
+        assert_eq!(feedback.items[0].get_title(), "hello world");
+        assert_eq!(feedback.items[0].get_subtitle(), Some("4.6 xyz 2"));
+        assert_eq!(simulator.max_reruns(), 3);
+    }
+
+    #[test]
+    fn test_run_failures() {
+        let dir = TempDir::new("simulator_failures");
+        let broken = dir.script("broken", "echo 'not json'");
+        let simulator = Simulator::new(SimulatorProfile::default().executable(&broken));
+        assert!(simulator.run("", &HashMap::new()).is_err());
+
+        let failing = dir.script("failing", "echo oops >&2; exit 3");
+        let simulator = Simulator::new(SimulatorProfile::default().executable(&failing));
+        let err = simulator.run("", &HashMap::new()).err().unwrap();
+        assert!(err.to_string().contains("oops"));
+
+        assert!(Simulator::new(SimulatorProfile::default())
+            .run("", &HashMap::new())
+            .is_err());
+    }
+}